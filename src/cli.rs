@@ -0,0 +1,873 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use cgmath::{Deg, EuclideanSpace, Point3, Vector3};
+use image::{open, GenericImageView};
+use log::debug;
+use rayon::ThreadPoolBuilder;
+
+use cpuengine::geometry::common::minmax;
+use cpuengine::model::{self, Format};
+use cpuengine::obj::{NormalSettings, NormalWeighting};
+use cpuengine::render::common::{NormalMap, NormalSpace, SpecularMap};
+use cpuengine::render::shader::{
+    FlatShader, GouraudShader, NormalShader, PhongShader, Shader, SolidShader, ToonShader, Uniforms,
+};
+use cpuengine::render::{
+    Attenuation, Camera, Light, LightKind, Material, Projection, Scene, ShadowSettings,
+};
+use cpuengine::{format_from_name, Error, Line, Mesh, Object, Renderer, Result, Triangle, PNG};
+
+const COLOR: [u8; 3] = [255, 255, 255];
+/// Largest `--shadow-size` accepted, which keeps each shadow map at 2 GiB.
+const MAX_SHADOW_SIZE: u32 = 16384;
+
+pub const USAGE: &str = "\
+Usage: cpuengine <command> [options] [arguments]
+
+Commands:
+  render <model>                 Render an OBJ, STL, PLY or glTF model
+  line <x0 y0 z0 x1 y1 z1>       Draw a line between two screen coordinates
+  triangle <x0 y0 z0 ... z2>     Fill a triangle given three screen coordinates
+  info <model>                   Print statistics about a model
+  convert <model> <output>       Write a model as OBJ, PLY or STL, picked from
+                                 the output extension
+
+Options:
+  -t, --texture <path>           Diffuse texture for `render`, replacing the
+                                 textures of the model's materials [default:
+                                 the `map_Kd` of each material, if any]
+  -n, --normal-map <path>        Normal map for `render` [default: the
+                                 `_nm_tangent` or `_nm` file next to the
+                                 texture, if any]
+      --normal-space <space>     Space of the normal map: object or tangent
+                                 [default: tangent for `_nm_tangent` files,
+                                 object otherwise]
+      --specular-map <path>      Specular map for `render` [default: the
+                                 `_spec` file next to the texture, if any]
+      --specular <r,g,b>         Specular highlight color [default: the
+                                 material's, or 0.5,0.5,0.5]
+      --shininess <exponent>     Blinn-Phong specular exponent [default: the
+                                 material's, or 32]
+  -o, --output <path>            Image to write [default: foo.png]
+  -s, --size <width>x<height>    Image resolution [default: 1000x1000]
+  -f, --format <format>          Image format: png, jpg, bmp, gif, ico or ppm
+                                 [default: picked from the output extension]
+  -c, --camera <x,y,z>           Eye position for a perspective camera
+                                 [default: orthographic view down -z]
+      --target <x,y,z>           Point the camera looks at [default: 0,0,0]
+      --fov <degrees>            Vertical field of view [default: 45]
+      --orthographic             Use a parallel projection
+  -l, --light <light>            Add a light, replacing the default headlight.
+                                 May be repeated. One of:
+                                   directional:<dx,dy,dz>
+                                   point:<x,y,z>
+                                   spot:<x,y,z>:<dx,dy,dz>
+                                 followed by any of :color=<r,g,b>,
+                                 :intensity=<value>, :cone=<inner,outer> (spot
+                                 angles in degrees) or :attenuation=<c,l,q>
+      --ambient <r,g,b>          Ambient light [default: 0,0,0]
+      --shadows                  Cast shadows from directional and spot lights
+      --shadow-size <pixels>     Shadow map resolution, up to 16384 [default:
+                                 1024]
+      --shadow-bias <depth>      Shadow depth bias [default: 0.002]
+      --shadow-pcf <radius>      Shadow filter radius in texels [default: 1]
+      --normals <weighting>      Weighting of generated normals: area or angle
+                                 [default: angle]
+      --crease-angle <degrees>   Largest angle between faces smoothed together
+                                 when generating normals [default: 60]
+      --show <names>             Only draw these comma separated objects or
+                                 groups of the model
+      --hide <names>             Skip these comma separated objects or groups
+      --no-cache                 Parse OBJ models instead of loading the
+                                 binary cache next to them (`<model>.cache`),
+                                 and do not write one
+      --shader <shader>          Shading: flat, gouraud, phong, toon or normals
+                                 [default: flat]
+      --threads <count>          Threads rendering tiles of the image in
+                                 parallel; 1 renders on a single thread
+                                 [default: one per core]
+  -h, --help                     Print this message
+";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render,
+    Line,
+    Triangle,
+    Info,
+    Convert,
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub arguments: Vec<String>,
+    pub texture: Option<String>,
+    pub normal_map: Option<String>,
+    pub normal_space: Option<NormalSpace>,
+    pub specular_map: Option<String>,
+    pub specular: Option<Vector3<f64>>,
+    pub shininess: Option<f64>,
+    pub output: Option<String>,
+    pub format: Option<String>,
+    pub size: (u32, u32),
+    pub camera: Option<Vector3<f64>>,
+    pub target: Option<Vector3<f64>>,
+    pub fov: Option<f64>,
+    pub orthographic: bool,
+    pub shader: String,
+    pub lights: Vec<Light>,
+    pub ambient: Option<Vector3<f64>>,
+    pub shadows: Option<ShadowSettings>,
+    pub show: Vec<String>,
+    pub hide: Vec<String>,
+    pub normals: Option<NormalSettings>,
+    pub cache: bool,
+    pub threads: Option<usize>,
+}
+
+impl Options {
+    /// Parses the command line, not including the program name.
+    pub fn parse(args: &[String]) -> Result<Options> {
+        let mut command: Option<Command> = None;
+        let mut options = Options {
+            command: Command::Help,
+            arguments: Vec::new(),
+            texture: None,
+            normal_map: None,
+            normal_space: None,
+            specular_map: None,
+            specular: None,
+            shininess: None,
+            output: None,
+            format: None,
+            size: (1000, 1000),
+            camera: None,
+            target: None,
+            fov: None,
+            orthographic: false,
+            shader: String::from("flat"),
+            lights: Vec::new(),
+            ambient: None,
+            shadows: None,
+            show: Vec::new(),
+            hide: Vec::new(),
+            normals: None,
+            cache: true,
+            threads: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| Error::Argument(format!("missing value for {}", name)))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    options.command = Command::Help;
+                    return Ok(options);
+                }
+                "-t" | "--texture" => options.texture = Some(value(arg)?),
+                "-n" | "--normal-map" => options.normal_map = Some(value(arg)?),
+                "--normal-space" => {
+                    options.normal_space = Some(match value(arg)?.as_str() {
+                        "object" => NormalSpace::Object,
+                        "tangent" => NormalSpace::Tangent,
+                        space => {
+                            return Err(Error::Argument(format!(
+                                "unknown normal map space: `{}`",
+                                space
+                            )))
+                        }
+                    })
+                }
+                "--specular-map" => options.specular_map = Some(value(arg)?),
+                "--specular" => options.specular = Some(parse_color(arg, &value(arg)?)?),
+                "--shininess" => options.shininess = Some(parse_number(arg, &value(arg)?)?),
+                "-o" | "--output" => options.output = Some(value(arg)?),
+                "-f" | "--format" => options.format = Some(value(arg)?),
+                "-s" | "--size" => options.size = parse_size(&value(arg)?)?,
+                "-c" | "--camera" => options.camera = Some(parse_vector(arg, &value(arg)?)?),
+                "--target" => options.target = Some(parse_vector(arg, &value(arg)?)?),
+                "--fov" => options.fov = Some(parse_number(arg, &value(arg)?)?),
+                "--orthographic" => options.orthographic = true,
+                "--no-cache" => options.cache = false,
+                "--threads" => {
+                    let threads = parse_number(arg, &value(arg)?)?;
+                    if threads == 0 {
+                        return Err(Error::Argument(String::from(
+                            "--threads must be at least 1",
+                        )));
+                    }
+                    options.threads = Some(threads);
+                }
+                "--shader" => options.shader = value(arg)?,
+                "-l" | "--light" => options.lights.push(parse_light(&value(arg)?)?),
+                "--shadows" => {
+                    options.shadows.get_or_insert_with(ShadowSettings::default);
+                }
+                "--shadow-size" => {
+                    let resolution = parse_number(arg, &value(arg)?)?;
+                    if resolution == 0 || resolution > MAX_SHADOW_SIZE {
+                        return Err(Error::Argument(format!(
+                            "--shadow-size must be between 1 and {}",
+                            MAX_SHADOW_SIZE
+                        )));
+                    }
+                    options
+                        .shadows
+                        .get_or_insert_with(ShadowSettings::default)
+                        .resolution = resolution;
+                }
+                "--shadow-bias" => {
+                    options
+                        .shadows
+                        .get_or_insert_with(ShadowSettings::default)
+                        .bias = parse_number(arg, &value(arg)?)?
+                }
+                "--shadow-pcf" => {
+                    options
+                        .shadows
+                        .get_or_insert_with(ShadowSettings::default)
+                        .pcf_radius = parse_number(arg, &value(arg)?)?
+                }
+                "--ambient" => options.ambient = Some(parse_color(arg, &value(arg)?)?),
+                "--normals" => {
+                    let weighting = match value(arg)?.as_str() {
+                        "area" => NormalWeighting::Area,
+                        "angle" => NormalWeighting::Angle,
+                        weighting => {
+                            return Err(Error::Argument(format!(
+                                "unknown normal weighting: `{}`",
+                                weighting
+                            )))
+                        }
+                    };
+                    options
+                        .normals
+                        .get_or_insert_with(Default::default)
+                        .weighting = weighting;
+                }
+                "--crease-angle" => {
+                    let angle = Deg(parse_number(arg, &value(arg)?)?);
+                    options
+                        .normals
+                        .get_or_insert_with(Default::default)
+                        .crease_angle = angle;
+                }
+                "--show" => options
+                    .show
+                    .extend(value(arg)?.split(',').map(String::from)),
+                "--hide" => options
+                    .hide
+                    .extend(value(arg)?.split(',').map(String::from)),
+                // Negative coordinates are arguments rather than flags.
+                flag if flag.starts_with('-') && flag.parse::<f64>().is_err() => {
+                    return Err(Error::Argument(format!("unknown option: {}", flag)));
+                }
+                _ if command.is_none() => {
+                    command = Some(match arg.as_str() {
+                        "render" | "object" => Command::Render,
+                        "line" => Command::Line,
+                        "triangle" => Command::Triangle,
+                        "info" => Command::Info,
+                        "convert" => Command::Convert,
+                        "help" => Command::Help,
+                        _ => return Err(Error::Argument(format!("unknown command: {}", arg))),
+                    })
+                }
+                _ => options.arguments.push(arg.clone()),
+            }
+        }
+
+        options.command =
+            command.ok_or_else(|| Error::Argument(String::from("no command given")))?;
+        Ok(options)
+    }
+
+    fn argument<T: FromStr>(&self, index: usize, name: &str) -> Result<T> {
+        let value = self
+            .arguments
+            .get(index)
+            .ok_or_else(|| Error::Argument(format!("missing argument: {}", name)))?;
+        value
+            .parse::<T>()
+            .map_err(|_| Error::Argument(format!("invalid value for {}: `{}`", name, value)))
+    }
+
+    fn expect_arguments(&self, count: usize) -> Result<()> {
+        if self.arguments.len() > count {
+            return Err(Error::Argument(format!(
+                "unexpected argument: {}",
+                self.arguments[count]
+            )));
+        }
+        Ok(())
+    }
+
+    fn vertex(&self, index: usize) -> Result<Vector3<f64>> {
+        Ok(Vector3::new(
+            self.argument(index * 3, &format!("x{}", index))?,
+            self.argument(index * 3 + 1, &format!("y{}", index))?,
+            self.argument(index * 3 + 2, &format!("z{}", index))?,
+        ))
+    }
+
+    fn camera(&self) -> Camera {
+        let mut camera = match self.camera {
+            Some(eye) => Camera::new(
+                Point3::from_vec(eye),
+                Point3::new(0., 0., 0.),
+                Vector3::unit_y(),
+            ),
+            None => Camera::default(),
+        };
+        if let Some(target) = self.target {
+            camera.target = Point3::from_vec(target);
+        }
+        if let Some(fov) = self.fov {
+            camera.projection = Projection::Perspective { fovy: Deg(fov) };
+        }
+        if self.orthographic {
+            camera.projection = Projection::Orthographic { height: 2. };
+        }
+        camera
+    }
+
+    fn scene(&self) -> Scene {
+        let camera = self.camera();
+        let mut scene = if self.lights.is_empty() {
+            Scene::new(camera)
+        } else {
+            Scene {
+                lights: self.lights.clone(),
+                ..Scene::unlit(camera)
+            }
+        };
+        if let Some(ambient) = self.ambient {
+            scene.ambient = ambient;
+        }
+        scene.shadows = self.shadows;
+        scene
+    }
+
+    /// Opens the normal map given on the command line, or the one found next
+    /// to the diffuse `texture`.
+    fn normal_map(&self, texture: Option<&str>) -> Result<Option<NormalMap>> {
+        let (path, space) = match &self.normal_map {
+            Some(path) => {
+                let space = if path.contains("_nm_tangent") {
+                    NormalSpace::Tangent
+                } else {
+                    NormalSpace::Object
+                };
+                (PathBuf::from(path), space)
+            }
+            None => match texture.and_then(NormalMap::find) {
+                Some(found) => found,
+                None => return Ok(None),
+            },
+        };
+        let space = self.normal_space.unwrap_or(space);
+        Ok(Some(NormalMap::open(path, space)?))
+    }
+
+    /// Opens the specular map given on the command line, or the one found
+    /// next to the diffuse `texture`.
+    fn specular_map(&self, texture: Option<&str>) -> Result<Option<SpecularMap>> {
+        let path = match &self.specular_map {
+            Some(path) => PathBuf::from(path),
+            None => match texture.and_then(SpecularMap::find) {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(SpecularMap::open(path)?))
+    }
+
+    fn renderer(&self) -> Result<PNG> {
+        debug!("Starting render");
+        let (width, height) = self.size;
+        let mut renderer: PNG = Renderer::new(width, height);
+        if let Some(output) = &self.output {
+            renderer = renderer.with_output(output);
+        }
+        if let Some(format) = &self.format {
+            let format = format_from_name(format)
+                .ok_or_else(|| Error::Argument(format!("unknown image format: `{}`", format)))?;
+            renderer = renderer.with_format(format);
+        }
+        Ok(renderer)
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse::<T>()
+        .map_err(|_| Error::Argument(format!("invalid value for {}: `{}`", name, value)))
+}
+
+/// Parses a comma separated `x,y,z` triple.
+fn parse_vector(name: &str, vector: &str) -> Result<Vector3<f64>> {
+    let invalid = || Error::Argument(format!("invalid value for {}: `{}`", name, vector));
+    let parts = vector
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<f64>, _>>()
+        .map_err(|_| invalid())?;
+    match parts.as_slice() {
+        [x, y, z] => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses an `r,g,b` color, or a single value for a grey one.
+fn parse_color(name: &str, color: &str) -> Result<Vector3<f64>> {
+    match color.parse::<f64>() {
+        Ok(value) => Ok(Vector3::new(value, value, value)),
+        Err(_) => parse_vector(name, color),
+    }
+}
+
+/// Parses a light description, see `USAGE`.
+fn parse_light(light: &str) -> Result<Light> {
+    let invalid = |reason: &str| Error::Argument(format!("invalid light `{}`: {}", light, reason));
+    let mut fields = light.split(':');
+    let kind = fields.next().unwrap_or_default();
+    let mut vector = || match fields.next() {
+        Some(field) => parse_vector("--light", field),
+        None => Err(invalid("missing coordinates")),
+    };
+
+    let mut light = match kind {
+        "directional" => Light::directional(vector()?),
+        "point" => Light::point(vector()?),
+        "spot" => Light::spot(vector()?, vector()?, Deg(20.), Deg(30.)),
+        _ => return Err(invalid("unknown kind")),
+    };
+
+    for field in fields {
+        let mut parts = field.splitn(2, '=');
+        let (key, value) = (parts.next().unwrap_or_default(), parts.next());
+        let value = value.ok_or_else(|| invalid("expected key=value"))?;
+        match key {
+            "color" => light = light.with_color(parse_color("--light color", value)?),
+            "intensity" => {
+                light = light
+                    .with_intensity(value.parse::<f64>().map_err(|_| invalid("bad intensity"))?)
+            }
+            "cone" => {
+                let cone: Vec<f64> = value
+                    .split(',')
+                    .map(|angle| angle.parse::<f64>())
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|_| invalid("bad cone"))?;
+                match (&mut light.kind, cone.as_slice()) {
+                    (LightKind::Spot { inner, outer, .. }, [a, b]) => {
+                        *inner = Deg(*a);
+                        *outer = Deg(*b);
+                    }
+                    _ => return Err(invalid("cone needs a spot light and two angles")),
+                }
+            }
+            "attenuation" => {
+                let factors = parse_vector("--light attenuation", value)?;
+                light = light.with_attenuation(Attenuation {
+                    constant: factors.x,
+                    linear: factors.y,
+                    quadratic: factors.z,
+                });
+            }
+            _ => return Err(invalid("unknown key")),
+        }
+    }
+    Ok(light)
+}
+
+/// Parses `WIDTHxHEIGHT`, or a single number for a square image.
+fn parse_size(size: &str) -> Result<(u32, u32)> {
+    let invalid = || Error::Argument(format!("invalid size: `{}`", size));
+    let mut parts = size.split('x').map(|part| part.parse::<u32>());
+    let size = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(side)), None, None) => (side, side),
+        (Some(Ok(width)), Some(Ok(height)), None) => (width, height),
+        _ => return Err(invalid()),
+    };
+    if size.0 == 0 || size.1 == 0 {
+        return Err(invalid());
+    }
+    Ok(size)
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    match options.command {
+        Command::Render => render_model(options),
+        Command::Line => render_line(options),
+        Command::Triangle => render_triangle(options),
+        Command::Info => info(options),
+        Command::Convert => convert(options),
+        Command::Help => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
+
+fn render_model(options: &Options) -> Result<()> {
+    let mut renderer = options.renderer()?;
+    options.expect_arguments(1)?;
+    let model_path: String = options.argument(0, "model")?;
+    let mut mesh = load_model(options, &model_path)?;
+    let texture_path = options.texture.as_deref();
+    if let Some(texture_path) = texture_path {
+        mesh = mesh.with_texture(open(texture_path)?.flipv());
+    }
+    if let Some(normal_map) = options.normal_map(texture_path)? {
+        mesh = mesh.with_normal_map(normal_map);
+    }
+    if let Some(specular_map) = options.specular_map(texture_path)? {
+        mesh = mesh.with_specular_map(specular_map);
+    }
+    for material in mesh.materials_mut() {
+        if let Some(specular) = options.specular {
+            material.specular = specular;
+        }
+        if let Some(shininess) = options.shininess {
+            material.shininess = shininess;
+        }
+    }
+    let scene = options.scene();
+    let renderer = &mut renderer;
+    match options.shader.as_str() {
+        "flat" => draw(options, &mesh, renderer, &scene, &FlatShader)?,
+        "gouraud" => draw(options, &mesh, renderer, &scene, &GouraudShader)?,
+        "phong" => draw(options, &mesh, renderer, &scene, &PhongShader)?,
+        "toon" => draw(options, &mesh, renderer, &scene, &ToonShader::default())?,
+        "normals" => draw(options, &mesh, renderer, &scene, &NormalShader)?,
+        shader => return Err(Error::Argument(format!("unknown shader: `{}`", shader))),
+    };
+    renderer.render()
+}
+
+/// Renders `mesh` on the number of threads given by `--threads`, taking the
+/// single threaded path for one thread.
+fn draw<S>(
+    options: &Options,
+    mesh: &Mesh,
+    renderer: &mut PNG,
+    scene: &Scene,
+    shader: &S,
+) -> Result<bool>
+where
+    S: Shader + Sync,
+    S::Varying: Send + Sync,
+{
+    match options.threads {
+        Some(1) => mesh.render_with(renderer, scene, shader),
+        Some(threads) => ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|error| {
+                Error::Argument(format!("unable to start {} threads: {}", threads, error))
+            })?
+            .install(|| mesh.render_parallel(renderer, scene, shader)),
+        None => mesh.render_parallel(renderer, scene, shader),
+    }
+}
+
+fn convert(options: &Options) -> Result<()> {
+    options.expect_arguments(2)?;
+    let model_path: String = options.argument(0, "model")?;
+    let output: String = options.argument(1, "output")?;
+    let mesh = load_model(options, &model_path)?;
+    model::save(&mesh, &output)
+}
+
+/// Loads a model in any format, applying the OBJ specific options to OBJ
+/// models and rejecting them for the others.
+fn load_model(options: &Options, path: &str) -> Result<Mesh> {
+    if Format::from_path(Path::new(path)) == Format::Obj {
+        return load_obj(options, path);
+    }
+    if !options.show.is_empty() || !options.hide.is_empty() || options.normals.is_some() {
+        return Err(Error::Argument(String::from(
+            "`--show`, `--hide`, `--normals` and `--crease-angle` only apply to OBJ models",
+        )));
+    }
+    model::load(path)
+}
+
+/// Loads an OBJ model, applying the OBJ specific options.
+fn load_obj(options: &Options, path: &str) -> Result<Mesh> {
+    let mut object = open_obj(options, path)?;
+    if let Some(settings) = options.normals {
+        object = object.with_normal_settings(settings);
+    }
+    if let Some(name) = options
+        .show
+        .iter()
+        .chain(&options.hide)
+        .find(|name| object.parts().all(|part| part.name != **name))
+    {
+        return Err(Error::Argument(format!(
+            "no object or group called `{}`",
+            name
+        )));
+    }
+    if !options.show.is_empty() {
+        let names: Vec<&str> = options.show.iter().map(String::as_str).collect();
+        object.show_only(&names);
+    }
+    for name in &options.hide {
+        object.hide(name);
+    }
+    Ok(object.to_mesh())
+}
+
+/// Loads an OBJ file, through its cache unless `--no-cache` is given.
+fn open_obj(options: &Options, path: &str) -> Result<Object> {
+    if options.cache {
+        Object::load_cached(path)
+    } else {
+        Object::new(path)
+    }
+}
+
+fn render_triangle(options: &Options) -> Result<()> {
+    let mut renderer = options.renderer()?;
+    options.expect_arguments(9)?;
+    let (vertex0, vertex1, vertex2) = (options.vertex(0)?, options.vertex(1)?, options.vertex(2)?);
+
+    let (width, height) = renderer.get_size();
+    let scene = Scene::default();
+    let uniforms = Uniforms::new(&scene, width, height);
+
+    let triangle = Triangle::new(vertex0, vertex1, vertex2, [(); 3])?;
+    triangle.render(&mut renderer, &SolidShader { color: COLOR }, &uniforms)?;
+    renderer.render()
+}
+
+fn render_line(options: &Options) -> Result<()> {
+    let mut renderer = options.renderer()?;
+    options.expect_arguments(6)?;
+    let line = Line::new(options.vertex(0)?, options.vertex(1)?, COLOR)?;
+    line.render(&mut renderer);
+    renderer.render()
+}
+
+fn info(options: &Options) -> Result<()> {
+    options.expect_arguments(1)?;
+    let object_path: String = options.argument(0, "model")?;
+    if Format::from_path(Path::new(&object_path)) != Format::Obj {
+        return mesh_info(&object_path, &model::load(&object_path)?);
+    }
+    let object = open_obj(options, &object_path)?;
+
+    let triangles: usize = object.triangles.iter().map(Vec::len).sum();
+    println!("{}", object_path);
+    println!("  vertices:            {}", object.vertices.len());
+    println!("  texture coordinates: {}", object.textures.len());
+    println!("  normals:             {}", object.normals.len());
+    println!("  faces:               {}", object.faces.len());
+    println!("  triangles:           {}", triangles);
+    if !object.vertices.is_empty() {
+        let (min, max) = minmax(&object.vertices);
+        println!(
+            "  bounds:              ({}, {}, {}) - ({}, {}, {})",
+            min.x, min.y, min.z, max.x, max.y, max.z
+        );
+    }
+    for (kind, parts) in &[("objects", &object.objects), ("groups", &object.groups)] {
+        if parts.is_empty() {
+            continue;
+        }
+        println!("  {:<21}{}", format!("{}:", kind), parts.len());
+        for part in parts.iter() {
+            println!("    {}: {} faces", part.name, part.faces.len());
+        }
+    }
+    if !object.lines.is_empty() || !object.points.is_empty() {
+        println!("  lines:               {}", object.lines.len());
+        println!("  points:              {}", object.points.len());
+    }
+    print_materials(&object.materials);
+    Ok(())
+}
+
+/// Prints statistics about a model loaded directly as a mesh.
+fn mesh_info(path: &str, mesh: &Mesh) -> Result<()> {
+    println!("{}", path);
+    println!("  vertices:            {}", mesh.positions.len());
+    println!("  triangles:           {}", mesh.triangles.len());
+    if !mesh.positions.is_empty() {
+        let (min, max) = minmax(&mesh.positions);
+        println!(
+            "  bounds:              ({}, {}, {}) - ({}, {}, {})",
+            min.x, min.y, min.z, max.x, max.y, max.z
+        );
+    }
+    print_materials(&mesh.materials);
+    Ok(())
+}
+
+fn print_materials(materials: &[Material]) {
+    println!("  materials:           {}", materials.len());
+    for material in materials {
+        let name = if material.name.is_empty() {
+            "(default)"
+        } else {
+            &material.name
+        };
+        match &material.diffuse_map {
+            Some(texture) => {
+                let (width, height) = texture.dimensions();
+                println!("    {}: {}x{} texture", name, width, height);
+            }
+            None => println!("    {}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn parse_render() {
+        let options =
+            parse(&["render", "head.obj", "-t", "head.tga", "--size", "640x480"]).unwrap();
+
+        assert_eq!(options.command, Command::Render);
+        assert_eq!(options.arguments, vec!["head.obj"]);
+        assert_eq!(options.texture, Some(String::from("head.tga")));
+        assert_eq!(options.size, (640, 480));
+
+        let options = parse(&["render", "a.obj", "--show", "body,arm", "--hide", "eyes"]).unwrap();
+        assert_eq!(options.show, vec!["body", "arm"]);
+        assert_eq!(options.hide, vec!["eyes"]);
+
+        let options = parse(&[
+            "render",
+            "a.obj",
+            "--crease-angle",
+            "30",
+            "--normals",
+            "area",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.normals,
+            Some(NormalSettings {
+                weighting: NormalWeighting::Area,
+                crease_angle: Deg(30.),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_camera() {
+        let options = parse(&["render", "a.obj", "--camera", "1,2,3", "--fov", "60"]).unwrap();
+        let camera = options.camera();
+
+        assert_eq!(camera.eye, Point3::new(1., 2., 3.));
+        assert_eq!(
+            camera.projection,
+            Projection::Perspective { fovy: Deg(60.) }
+        );
+        assert!(parse(&["render", "a.obj", "--camera", "1,2"]).is_err());
+    }
+
+    #[test]
+    fn parse_material() {
+        let options = parse(&[
+            "render",
+            "a.obj",
+            "--specular",
+            "1,0.5,0",
+            "--shininess",
+            "64",
+            "--specular-map",
+            "a_spec.tga",
+        ])
+        .unwrap();
+
+        assert_eq!(options.specular, Some(Vector3::new(1., 0.5, 0.)));
+        assert_eq!(options.shininess, Some(64.));
+        assert_eq!(options.specular_map, Some(String::from("a_spec.tga")));
+        assert!(parse(&["render", "a.obj", "--shininess", "shiny"]).is_err());
+    }
+
+    #[test]
+    fn parse_negative_coordinates() {
+        let options = parse(&["line", "-1", "0", "0", "5", "-2.5", "0"]).unwrap();
+
+        assert_eq!(options.command, Command::Line);
+        assert_eq!(options.vertex(1).unwrap(), Vector3::new(5., -2.5, 0.));
+    }
+
+    #[test]
+    fn parse_lights() {
+        let options = parse(&[
+            "render",
+            "a.obj",
+            "--light",
+            "point:0,2,0:color=1,0.5,0:intensity=2",
+            "-l",
+            "spot:0,0,5:0,0,-1:cone=10,15",
+            "--ambient",
+            "0.1",
+        ])
+        .unwrap();
+        let scene = options.scene();
+
+        assert_eq!(scene.lights.len(), 2);
+        assert_eq!(scene.lights[0].color, Vector3::new(1., 0.5, 0.));
+        assert_eq!(scene.lights[0].intensity, 2.);
+        assert_eq!(scene.ambient, Vector3::new(0.1, 0.1, 0.1));
+        assert!(parse(&["render", "a.obj", "--light", "lamp:0,0,0"]).is_err());
+        assert!(parse(&["render", "a.obj", "--light", "point:0,0,0:cone=1,2"]).is_err());
+    }
+
+    #[test]
+    fn parse_convert() {
+        let options = parse(&["convert", "a.stl", "a.ply"]).unwrap();
+        assert_eq!(options.command, Command::Convert);
+        assert_eq!(options.arguments, vec!["a.stl", "a.ply"]);
+        assert!(options.cache);
+        assert!(!parse(&["info", "a.obj", "--no-cache"]).unwrap().cache);
+    }
+
+    #[test]
+    fn parse_threads() {
+        let options = parse(&["render", "a.obj", "--threads", "4"]).unwrap();
+        assert_eq!(options.threads, Some(4));
+        assert_eq!(parse(&["render", "a.obj"]).unwrap().threads, None);
+        assert!(parse(&["render", "a.obj", "--threads", "0"]).is_err());
+    }
+
+    #[test]
+    fn parse_shadow_size() {
+        let options = parse(&["render", "a.obj", "--shadow-size", "512"]).unwrap();
+        assert_eq!(options.shadows.unwrap().resolution, 512);
+        assert!(parse(&["render", "a.obj", "--shadow-size", "0"]).is_err());
+        assert!(parse(&["render", "a.obj", "--shadow-size", "65536"]).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["paint"]).is_err());
+        assert!(parse(&["render", "--bogus"]).is_err());
+        assert!(parse(&["render", "--size", "0x10"]).is_err());
+        assert_eq!(parse(&["render", "--help"]).unwrap().command, Command::Help);
+    }
+}
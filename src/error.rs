@@ -0,0 +1,103 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use image::ImageError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A model file contained something that could not be understood.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A binary model file was truncated or malformed.
+    Format { path: PathBuf, message: String },
+    /// A face referenced an element that does not exist.
+    InvalidIndex {
+        kind: &'static str,
+        index: i64,
+        len: usize,
+    },
+    /// A texture could not be opened or decoded.
+    Image(ImageError),
+    /// The rendered image could not be encoded.
+    Encode(ImageError),
+    /// A value passed in by the caller was not usable.
+    Argument(String),
+}
+
+impl Error {
+    pub(crate) fn parse<P: Into<PathBuf>, M: Into<String>>(
+        path: P,
+        line: usize,
+        column: usize,
+        message: M,
+    ) -> Error {
+        Error::Parse {
+            path: path.into(),
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn format<P: Into<PathBuf>, M: Into<String>>(path: P, message: M) -> Error {
+        Error::Format {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, message),
+            Error::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::InvalidIndex { kind, index, len } => write!(
+                f,
+                "{} index {} is out of range ({} defined)",
+                kind, index, len
+            ),
+            Error::Image(err) => write!(f, "unable to load image: {}", err),
+            Error::Encode(err) => write!(f, "unable to encode image: {}", err),
+            Error::Argument(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Image(err) | Error::Encode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Error {
+        Error::Image(err)
+    }
+}
@@ -0,0 +1,190 @@
+use cgmath::{InnerSpace, Vector2, Vector3, Vector4};
+
+/// Distance in pixels beyond the edges of the image that triangles are
+/// clipped to. Triangles poking out less than this are left for the
+/// rasterizer to trim, which saves splitting them, and it is well inside the
+/// range the rasterizer's fixed point coordinates can hold.
+pub const GUARD_BAND: f64 = (1 << 20) as f64;
+
+/// A corner of a triangle clipped in homogeneous clip space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipVertex {
+    pub position: Vector4<f64>,
+    /// Weights of the unclipped triangle's corners at this point, for
+    /// interpolating its varyings.
+    pub weights: Vector3<f64>,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f64) -> ClipVertex {
+        ClipVertex {
+            position: self.position + (other.position - self.position) * t,
+            weights: self.weights + (other.weights - self.weights) * t,
+        }
+    }
+}
+
+/// Scale of the view volume's sides in clip space that reaches `GUARD_BAND`
+/// pixels beyond a `width` by `height` image.
+pub fn guard_band(width: u32, height: u32) -> Vector2<f64> {
+    Vector2::new(
+        1. + 2. * GUARD_BAND / f64::from(width.max(1)),
+        1. + 2. * GUARD_BAND / f64::from(height.max(1)),
+    )
+}
+
+/// Planes bounding the view volume, with its sides scaled by `guard`. A
+/// clip space position `p` is inside a plane when `plane · p >= 0`.
+fn planes(guard: Vector2<f64>) -> [Vector4<f64>; 6] {
+    [
+        // Near and far.
+        Vector4::new(0., 0., 1., 1.),
+        Vector4::new(0., 0., -1., 1.),
+        // Left, right, bottom and top.
+        Vector4::new(1., 0., 0., guard.x),
+        Vector4::new(-1., 0., 0., guard.x),
+        Vector4::new(0., 1., 0., guard.y),
+        Vector4::new(0., -1., 0., guard.y),
+    ]
+}
+
+/// Whether the clip space `position` is inside the view volume with its
+/// sides scaled by `guard`.
+pub fn inside(position: Vector4<f64>, guard: Vector2<f64>) -> bool {
+    planes(guard).iter().all(|plane| plane.dot(position) >= 0.)
+}
+
+/// Clips the triangle with clip space `corners` against the view volume
+/// with its sides scaled by `guard`, returning the corners of the convex
+/// polygon left in the same winding, or none if it is entirely outside.
+pub fn clip_triangle(corners: [Vector4<f64>; 3], guard: Vector2<f64>) -> Vec<ClipVertex> {
+    let mut polygon: Vec<ClipVertex> = corners
+        .iter()
+        .zip(&[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()])
+        .map(|(&position, &weights)| ClipVertex { position, weights })
+        .collect();
+
+    for plane in &planes(guard) {
+        let distances: Vec<f64> = polygon
+            .iter()
+            .map(|vertex| plane.dot(vertex.position))
+            .collect();
+        if distances.iter().all(|&distance| distance >= 0.) {
+            continue;
+        }
+
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (index, vertex) in polygon.iter().enumerate() {
+            let next = (index + 1) % polygon.len();
+            let (distance, next_distance) = (distances[index], distances[next]);
+            if distance >= 0. {
+                clipped.push(*vertex);
+            }
+            // Edges are always split from their inside end, so triangles
+            // sharing an edge get the same point on it.
+            if distance >= 0. && next_distance < 0. {
+                let t = distance / (distance - next_distance);
+                clipped.push(vertex.lerp(&polygon[next], t));
+            } else if distance < 0. && next_distance >= 0. {
+                let t = next_distance / (next_distance - distance);
+                clipped.push(polygon[next].lerp(vertex, t));
+            }
+        }
+        polygon = clipped;
+        if polygon.is_empty() {
+            break;
+        }
+    }
+    polygon
+}
+
+/// Clips the line from `start` to `end` in clip space against the view
+/// volume with its sides scaled by `guard`, returning the range of the
+/// interpolation parameter along it that is inside, or `None` if none is.
+pub fn clip_line(
+    start: Vector4<f64>,
+    end: Vector4<f64>,
+    guard: Vector2<f64>,
+) -> Option<(f64, f64)> {
+    let (mut t0, mut t1) = (0., 1.);
+    for plane in &planes(guard) {
+        let (distance, end_distance) = (plane.dot(start), plane.dot(end));
+        if distance < 0. && end_distance < 0. {
+            return None;
+        }
+        if distance < 0. {
+            t0 = f64::max(t0, distance / (distance - end_distance));
+        } else if end_distance < 0. {
+            t1 = f64::min(t1, distance / (distance - end_distance));
+        }
+    }
+    if t0 <= t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view() -> Vector2<f64> {
+        Vector2::new(1., 1.)
+    }
+
+    #[test]
+    fn inside_triangle_is_kept() {
+        let corners = [
+            Vector4::new(-0.5, -0.5, 0., 1.),
+            Vector4::new(0.5, -0.5, 0., 1.),
+            Vector4::new(0., 0.5, 0., 1.),
+        ];
+        let polygon = clip_triangle(corners, view());
+        assert_eq!(polygon.len(), 3);
+        assert_eq!(polygon[1].position, corners[1]);
+        assert_eq!(polygon[1].weights, Vector3::unit_y());
+    }
+
+    #[test]
+    fn near_plane() {
+        // One corner behind the eye with w < 0, as perspective projections
+        // give, leaves a quad in front of the near plane.
+        let corners = [
+            Vector4::new(0., 0., -2., -1.),
+            Vector4::new(-0.5, -0.5, 0., 1.),
+            Vector4::new(0.5, -0.5, 0., 1.),
+        ];
+        let polygon = clip_triangle(corners, view());
+        assert_eq!(polygon.len(), 4);
+        for vertex in &polygon {
+            assert!(vertex.position.z + vertex.position.w >= -1e-12);
+            assert!(vertex.position.w > 0.);
+            let weights = vertex.weights;
+            assert!((weights.x + weights.y + weights.z - 1.).abs() < 1e-12);
+            let interpolated =
+                corners[0] * weights.x + corners[1] * weights.y + corners[2] * weights.z;
+            assert!((interpolated - vertex.position).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn outside_triangle_is_dropped() {
+        let corners = [
+            Vector4::new(2., 0., 0., 1.),
+            Vector4::new(3., 0., 0., 1.),
+            Vector4::new(2., 1., 0., 1.),
+        ];
+        assert!(clip_triangle(corners, view()).is_empty());
+        assert_eq!(clip_triangle(corners, Vector2::new(4., 4.)).len(), 3);
+    }
+
+    #[test]
+    fn line() {
+        let (start, end) = (Vector4::new(-3., 0., 0., 1.), Vector4::new(1., 0., 0., 1.));
+        assert_eq!(clip_line(start, end, view()), Some((0.5, 1.)));
+        assert_eq!(clip_line(start, start, view()), None);
+        assert!(inside(end, view()));
+        assert!(!inside(start, view()));
+    }
+}
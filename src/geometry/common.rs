@@ -1,4 +1,4 @@
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector2, Vector3, Vector4};
 
 pub fn minmax(vectors: &[Vector3<f64>]) -> (Vector3<f64>, Vector3<f64>) {
     let mut max: Vector3<f64> = Vector3::new(vectors[0].x, vectors[0].y, vectors[0].z);
@@ -28,6 +28,139 @@ pub fn minmax(vectors: &[Vector3<f64>]) -> (Vector3<f64>, Vector3<f64>) {
     (min, max)
 }
 
+/// Direction of increasing `u` and `v` texture coordinates across a
+/// triangle, or `None` if its texture coordinates are degenerate.
+pub fn tangents(
+    positions: [Vector3<f64>; 3],
+    coords: [Vector3<f64>; 3],
+) -> Option<(Vector3<f64>, Vector3<f64>)> {
+    let (edge1, edge2) = (positions[1] - positions[0], positions[2] - positions[0]);
+    let (delta1, delta2) = (coords[1] - coords[0], coords[2] - coords[0]);
+
+    let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+    let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+    let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+    if tangent.magnitude2() < f64::EPSILON {
+        return None;
+    }
+    Some((tangent, bitangent))
+}
+
+/// Makes an accumulated tangent perpendicular to `normal`, returning it with
+/// the handedness of `bitangent` in `w`. Tangents that vanish are replaced by
+/// any direction perpendicular to the normal.
+pub fn orthogonal_tangent(
+    tangent: Vector3<f64>,
+    bitangent: Vector3<f64>,
+    normal: Vector3<f64>,
+) -> Vector4<f64> {
+    let normal = if normal.magnitude2() > f64::EPSILON {
+        normal.normalize()
+    } else {
+        Vector3::unit_z()
+    };
+    let mut tangent = tangent - normal * normal.dot(tangent);
+    if tangent.magnitude2() <= f64::EPSILON || !tangent.x.is_finite() {
+        tangent = normal.cross(if normal.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        });
+    }
+    let tangent = tangent.normalize();
+    let handedness = if normal.cross(tangent).dot(bitangent) < 0. {
+        -1.
+    } else {
+        1.
+    };
+    tangent.extend(handedness)
+}
+
+/// Splits a polygon into triangles by ear clipping, returning indices into
+/// `polygon`. The polygon is projected onto its average plane first, so it
+/// may be concave or slightly non-planar. Triangles keep the winding of the
+/// polygon.
+pub fn triangulate(polygon: &[Vector3<f64>]) -> Vec<[usize; 3]> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+    if polygon.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a sensible normal for non-planar polygons too.
+    let mut normal = Vector3::new(0., 0., 0.);
+    for (index, current) in polygon.iter().enumerate() {
+        let next = polygon[(index + 1) % polygon.len()];
+        normal += current.cross(next);
+    }
+    if normal.magnitude2() <= f64::EPSILON {
+        return fan(polygon.len());
+    }
+    let normal = normal.normalize();
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = normal.cross(axis).normalize();
+    let v = normal.cross(u);
+    let points: Vec<Vector2<f64>> = polygon
+        .iter()
+        .map(|point| Vector2::new(point.dot(u), point.dot(v)))
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(polygon.len() - 2);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&index| {
+            let (a, b, c) = (
+                remaining[(index + count - 1) % count],
+                remaining[index],
+                remaining[(index + 1) % count],
+            );
+            cross(points[a], points[b], points[c]) > 0.
+                && remaining.iter().all(|&other| {
+                    other == a
+                        || other == b
+                        || other == c
+                        || !in_triangle(points[other], points[a], points[b], points[c])
+                })
+        });
+        // Self-intersecting or degenerate polygons may have no ear left;
+        // clip anyway rather than loop forever.
+        let index = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(index + count - 1) % count],
+            remaining[index],
+            remaining[(index + 1) % count],
+        ]);
+        remaining.remove(index);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+/// Fan triangulation of a convex polygon with `count` vertices.
+fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count - 1).map(|index| [0, index, index + 1]).collect()
+}
+
+/// Twice the signed area of the triangle `abc`, positive when it is wound
+/// counter-clockwise.
+fn cross(a: Vector2<f64>, b: Vector2<f64>, c: Vector2<f64>) -> f64 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Whether `point` lies inside or on the counter-clockwise triangle `abc`.
+fn in_triangle(point: Vector2<f64>, a: Vector2<f64>, b: Vector2<f64>, c: Vector2<f64>) -> bool {
+    cross(a, b, point) >= 0. && cross(b, c, point) >= 0. && cross(c, a, point) >= 0.
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,4 +184,72 @@ mod tests {
         assert_eq!(min.z.abs() as i32, 7);
         assert_eq!(max.z.abs() as i32, 64);
     }
+
+    #[test]
+    fn test_tangents() {
+        let positions = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0., 2., 0.),
+            Vector3::new(0., 0., 2.),
+        ];
+        let coords = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 1., 0.),
+        ];
+
+        let (tangent, bitangent) = self::tangents(positions, coords).unwrap();
+
+        assert_eq!(tangent, Vector3::new(0., 2., 0.));
+        assert_eq!(bitangent, Vector3::new(0., 0., 2.));
+        assert!(self::tangents(positions, [coords[0]; 3]).is_none());
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        // An L shape, concave at the fourth vertex.
+        let polygon = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(2., 0., 0.),
+            Vector3::new(2., 1., 0.),
+            Vector3::new(1., 1., 0.),
+            Vector3::new(1., 2., 0.),
+            Vector3::new(0., 2., 0.),
+        ];
+        let triangles = triangulate(&polygon);
+
+        assert_eq!(triangles.len(), 4);
+        let area: f64 = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                (polygon[*b] - polygon[*a])
+                    .cross(polygon[*c] - polygon[*a])
+                    .z
+                    / 2.
+            })
+            .sum();
+        assert!((area - 3.).abs() < 1e-9);
+        // Every triangle is wound like the polygon, so none spill outside.
+        for [a, b, c] in &triangles {
+            assert!(
+                (polygon[*b] - polygon[*a])
+                    .cross(polygon[*c] - polygon[*a])
+                    .z
+                    > 0.
+            );
+        }
+    }
+
+    #[test]
+    fn triangulate_non_planar() {
+        let polygon = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0., 0., -1.),
+            Vector3::new(0., 1., -1.),
+            Vector3::new(0., 1., 0.1),
+        ];
+
+        assert_eq!(triangulate(&polygon).len(), 2);
+        assert_eq!(triangulate(&polygon[..3]), vec![[0, 1, 2]]);
+    }
 }
@@ -1,9 +1,11 @@
-use std::error;
+use std::error::Error;
 use std::fmt;
 
-use cgmath::Vector3;
+use cgmath::{Vector2, Vector3, Vector4};
 use log::debug;
 
+use crate::error;
+use crate::geometry::clip;
 use crate::render::Renderer;
 
 pub struct Line {
@@ -21,7 +23,15 @@ impl Line {
         vertex0: Vector3<f64>,
         vertex1: Vector3<f64>,
         color: [u8; 3],
-    ) -> Result<Line, Box<std::error::Error>> {
+    ) -> error::Result<Line> {
+        for vertex in &[vertex0, vertex1] {
+            if !(vertex.x.is_finite() && vertex.y.is_finite() && vertex.z.is_finite()) {
+                return Err(error::Error::Argument(format!(
+                    "line endpoint is not finite: ({}, {}, {})",
+                    vertex.x, vertex.y, vertex.z
+                )));
+            }
+        }
         let mut vertices_sorted_x: Vec<Vector3<f64>> = vec![vertex0, vertex1];
         vertices_sorted_x.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
         let mut vertices_sorted_y: Vec<Vector3<f64>> = vec![vertex0, vertex1];
@@ -38,23 +48,36 @@ impl Line {
         Ok(line)
     }
 
+    /// Draws the line between its screen space endpoints, clipped to the
+    /// renderer's image.
     pub fn render(&self, renderer: &mut impl Renderer) {
-        let steps: f64 = self.vertex0.x - self.vertex1.x;
-        for index in 0..steps.abs() as u32 {
-            let (x, y) = if self.vertex0.x < self.vertex1.x {
-                (
-                    self.vertex0.x + f64::from(index),
-                    self.vertex0.y + (f64::from(index) * self.slope),
-                )
-            } else {
-                (
-                    self.vertex0.x - f64::from(index),
-                    self.vertex1.y + ((steps - f64::from(index)) * self.slope),
-                )
-            };
-
-            let pixel: Vector3<f64> = Vector3::new(x, y, 0.0);
+        let (width, height) = renderer.get_size();
+        let (width, height) = (f64::from(width.max(1)), f64::from(height.max(1)));
+        let to_clip = |vertex: Vector3<f64>| {
+            Vector4::new(
+                2. * vertex.x / width - 1.,
+                2. * vertex.y / height - 1.,
+                0.,
+                1.,
+            )
+        };
+        let (t0, t1) = match clip::clip_line(
+            to_clip(self.vertex0),
+            to_clip(self.vertex1),
+            Vector2::new(1., 1.),
+        ) {
+            Some(range) => range,
+            None => return,
+        };
 
+        let delta = self.vertex1 - self.vertex0;
+        let (start, end) = (self.vertex0 + delta * t0, self.vertex0 + delta * t1);
+        // One pixel per step along the major axis.
+        let delta = end - start;
+        let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.);
+        for step in 0..=steps as u32 {
+            let position = start + delta * (f64::from(step) / steps);
+            let pixel = Vector3::new(position.x.round(), position.y.round(), position.z);
             renderer.set_pixel(pixel, self.color);
         }
     }
@@ -64,7 +87,7 @@ impl Line {
     }
 
     fn y_intercept(vertex0: Vector3<f64>, vertex1: Vector3<f64>) -> f64 {
-        (vertex0.y - (vertex0.x * Line::slope(vertex0, vertex1)))
+        vertex0.y - (vertex0.x * Line::slope(vertex0, vertex1))
     }
 
     pub fn in_line(&self, vertex: Vector3<f64>) -> bool {
@@ -108,12 +131,12 @@ impl fmt::Display for IntersectError {
     }
 }
 
-impl error::Error for IntersectError {
+impl Error for IntersectError {
     fn description(&self) -> &str {
         "Lines do not intersect."
     }
 
-    fn cause(&self) -> Option<&error::Error> {
+    fn cause(&self) -> Option<&dyn Error> {
         None
     }
 }
@@ -166,6 +189,14 @@ mod tests {
         assert_eq!(intersect.y as i32, 50);
     }
 
+    #[test]
+    fn non_finite_endpoints() {
+        let color = [255, 255, 255];
+        let origin = Vector3::new(0., 0., 0.);
+        assert!(Line::new(Vector3::new(f64::NAN, 0., 0.), origin, color).is_err());
+        assert!(Line::new(origin, Vector3::new(0., f64::INFINITY, 0.), color).is_err());
+    }
+
     #[test]
     fn intersect_error() {
         let color = [255, 255, 255];
@@ -1,3 +1,4 @@
+pub mod clip;
 pub mod common;
 pub mod line;
 pub mod triangle;
@@ -1,110 +1,462 @@
-use std::error;
+use cgmath::{Vector2, Vector3, Vector4};
 
-use cgmath::Vector3;
-use image::{DynamicImage, GenericImageView};
-use log::{debug, warn};
+use crate::error::Result;
+use crate::geometry::clip;
+use crate::render::shader::{Fragment, Shader, Uniforms, Varying};
+use crate::render::{Renderer, Tile};
 
-use crate::geometry::common::minmax;
-use crate::render::common as render_common;
-use crate::render::Renderer;
+/// Bits of sub-pixel precision that corners are snapped to before they are
+/// rasterized.
+const SUB_PIXEL_BITS: u32 = 8;
+const SUB_PIXEL: i64 = 1 << SUB_PIXEL_BITS;
 
-pub struct Triangle<'a> {
+/// Corners must be closer than this many pixels to the origin to be
+/// rasterized, which keeps the fixed point edge functions from overflowing.
+/// Triangles reaching further are first clipped to `CLIP_COORDINATE`.
+const MAX_COORDINATE: f64 = (1 << 21) as f64;
+const CLIP_COORDINATE: f64 = MAX_COORDINATE / 2.;
+
+/// A screen space triangle along with the vertex shader outputs at each corner.
+pub struct Triangle<V> {
     a: Vector3<f64>,
     b: Vector3<f64>,
     c: Vector3<f64>,
-    texture: &'a DynamicImage,
-    texture_vertices: &'a [Vector3<f64>],
-    intensity: f64,
+    inverse_w: Vector3<f64>,
+    varyings: [V; 3],
+    /// Fixed point edge functions of the triangle, or of the parts left
+    /// after clipping it, leaving out parts that cover no pixel centers.
+    parts: Vec<Setup>,
+}
+
+/// The edge functions of a triangle snapped to fixed point, for stepping
+/// across the pixels it covers.
+struct Setup {
+    /// Weights of the corners of the whole triangle at the corners of this
+    /// part, if it was clipped from it.
+    corners: Option<[Vector3<f64>; 3]>,
+    /// Edges opposite each corner, positive inside the triangle whichever
+    /// way it is wound.
+    edges: [Edge; 3],
+    /// Twice the area of the triangle, the sum of the edge functions.
+    area: i64,
+    /// Inclusive range of pixels whose centers may be covered.
+    min: [i64; 2],
+    max: [i64; 2],
+}
+
+/// The edge function `(end - start) × (p - start)` of the edge from `start`
+/// to `end`, in fixed point.
+#[derive(Clone, Copy)]
+struct Edge {
+    /// Change in the edge function from one pixel to the next along x and y.
+    step: [i64; 2],
+    /// Value of the edge function at pixel (0, 0).
+    origin: i64,
+    /// Smallest value of the edge function that counts as covered. Pixel
+    /// centers exactly on an edge are only drawn for top and left edges, so
+    /// pixels on an edge shared by two triangles are drawn once.
+    bias: i64,
+}
+
+impl Edge {
+    fn new(start: [i64; 2], end: [i64; 2]) -> Edge {
+        let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+        // With y pointing up and the inside of the triangle on the left of
+        // the edge, left edges point down and top edges point left.
+        let top_left = dy < 0 || (dy == 0 && dx < 0);
+        Edge {
+            step: [-dy * SUB_PIXEL, dx * SUB_PIXEL],
+            origin: dy * start[0] - dx * start[1],
+            bias: if top_left { 0 } else { 1 },
+        }
+    }
+
+    fn at(&self, x: i64, y: i64) -> i64 {
+        self.origin + self.step[0] * x + self.step[1] * y
+    }
 }
 
-impl<'a> Triangle<'a> {
+impl Setup {
+    /// Sets up the triangle with screen space `corners`, splitting it into
+    /// parts within range of the fixed point coordinates if needed.
+    fn parts(corners: [Vector3<f64>; 3]) -> Vec<Setup> {
+        let in_range = |corner: &Vector3<f64>| {
+            corner.x.abs() < MAX_COORDINATE && corner.y.abs() < MAX_COORDINATE
+        };
+        if corners.iter().all(in_range) {
+            return Setup::new([corners[0], corners[1], corners[2]], None)
+                .into_iter()
+                .collect();
+        }
+        if corners
+            .iter()
+            .any(|corner| !corner.x.is_finite() || !corner.y.is_finite())
+        {
+            return Vec::new();
+        }
+
+        // With `w` of 1, the sides of the clip space view volume scaled by
+        // `CLIP_COORDINATE` lie that many pixels from the origin.
+        let polygon = clip::clip_triangle(
+            [
+                Vector4::new(corners[0].x, corners[0].y, 0., 1.),
+                Vector4::new(corners[1].x, corners[1].y, 0., 1.),
+                Vector4::new(corners[2].x, corners[2].y, 0., 1.),
+            ],
+            Vector2::new(CLIP_COORDINATE, CLIP_COORDINATE),
+        );
+        (2..polygon.len())
+            .filter_map(|index| {
+                let part = [polygon[0], polygon[index - 1], polygon[index]];
+                Setup::new(
+                    [
+                        part[0].position.truncate(),
+                        part[1].position.truncate(),
+                        part[2].position.truncate(),
+                    ],
+                    Some([part[0].weights, part[1].weights, part[2].weights]),
+                )
+            })
+            .collect()
+    }
+
+    fn new(corners: [Vector3<f64>; 3], weights: Option<[Vector3<f64>; 3]>) -> Option<Setup> {
+        let snap = |value: f64| {
+            if value.abs() < MAX_COORDINATE {
+                Some((value * SUB_PIXEL as f64).round() as i64)
+            } else {
+                None
+            }
+        };
+        let mut fixed = [[0; 2]; 3];
+        for (fixed, corner) in fixed.iter_mut().zip(&corners) {
+            *fixed = [snap(corner.x)?, snap(corner.y)?];
+        }
+        let [a, b, c] = fixed;
+
+        let winding = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+        let edges = match winding {
+            0 => return None,
+            w if w > 0 => [Edge::new(b, c), Edge::new(c, a), Edge::new(a, b)],
+            _ => [Edge::new(c, b), Edge::new(a, c), Edge::new(b, a)],
+        };
+
+        // Pixel centers lie on whole pixel coordinates.
+        let ceil = |value: i64| (value + SUB_PIXEL - 1) >> SUB_PIXEL_BITS;
+        let floor = |value: i64| value >> SUB_PIXEL_BITS;
+        let min = [
+            ceil(a[0].min(b[0]).min(c[0])),
+            ceil(a[1].min(b[1]).min(c[1])),
+        ];
+        let max = [
+            floor(a[0].max(b[0]).max(c[0])),
+            floor(a[1].max(b[1]).max(c[1])),
+        ];
+        if min[0] > max[0] || min[1] > max[1] {
+            return None;
+        }
+
+        Some(Setup {
+            corners: weights,
+            edges,
+            area: winding.abs(),
+            min,
+            max,
+        })
+    }
+}
+
+impl<V: Varying> Triangle<V> {
     pub fn new(
         a: Vector3<f64>,
         b: Vector3<f64>,
         c: Vector3<f64>,
-        texture: &'a DynamicImage,
-        texture_vertices: &'a [Vector3<f64>],
-        intensity: f64,
-    ) -> Result<Triangle<'a>, Box<error::Error>> {
-        let triangle: Triangle<'a> = Triangle {
+        varyings: [V; 3],
+    ) -> Result<Triangle<V>> {
+        let triangle: Triangle<V> = Triangle {
             a,
             b,
             c,
-            texture,
-            texture_vertices,
-            intensity,
+            inverse_w: Vector3::new(1., 1., 1.),
+            varyings,
+            parts: Setup::parts([a, b, c]),
         };
         Ok(triangle)
     }
 
-    pub fn get_color(&self, barycenter: Vector3<f64>) -> [u8; 3] {
-        let mut coords = self.texture_vertices.to_vec();
+    /// Sets the clip space `w` of each corner so that varyings are
+    /// interpolated with perspective correction.
+    pub fn with_w(mut self, w: [f64; 3]) -> Triangle<V> {
+        self.inverse_w = Vector3::new(1. / w[0], 1. / w[1], 1. / w[2]);
+        self
+    }
 
-        for coord in coords.iter_mut() {
-            coord.x *= f64::from(self.texture.width());
-            coord.y *= f64::from(self.texture.height());
+    fn varying(&self, barycenter: Vector3<f64>) -> V {
+        let weights = Vector3::new(
+            barycenter.x * self.inverse_w.x,
+            barycenter.y * self.inverse_w.y,
+            barycenter.z * self.inverse_w.z,
+        );
+        let weights = weights / (weights.x + weights.y + weights.z);
+        V::weighted(
+            &self.varyings[0],
+            &self.varyings[1],
+            &self.varyings[2],
+            weights,
+        )
+    }
+
+    pub fn render<S: Shader<Varying = V>>(
+        &self,
+        renderer: &mut impl Renderer,
+        shader: &S,
+        uniforms: &Uniforms,
+    ) -> Result<bool> {
+        let (width, height) = renderer.get_size();
+        self.rasterize(
+            [0, 0],
+            [i64::from(width) - 1, i64::from(height) - 1],
+            shader,
+            uniforms,
+            |pixel, color| renderer.set_pixel(pixel, color),
+        );
+        Ok(true)
+    }
+
+    /// Draws the part of the triangle inside `tile`, giving the same pixels
+    /// as `render` does there.
+    pub fn render_tile<S: Shader<Varying = V>>(
+        &self,
+        tile: &mut Tile,
+        shader: &S,
+        uniforms: &Uniforms,
+    ) -> Result<bool> {
+        let min = [i64::from(tile.x), i64::from(tile.y)];
+        let max = [
+            min[0] + i64::from(tile.width) - 1,
+            min[1] + i64::from(tile.height) - 1,
+        ];
+        self.rasterize(min, max, shader, uniforms, |pixel, color| {
+            tile.set_pixel(pixel, color)
+        });
+        Ok(true)
+    }
+
+    /// Inclusive range of pixels that the triangle may cover, or `None` if
+    /// it covers none.
+    pub(crate) fn bounds(&self) -> Option<([i64; 2], [i64; 2])> {
+        self.parts.iter().fold(None, |bounds, part| match bounds {
+            None => Some((part.min, part.max)),
+            Some((min, max)) => Some((
+                [min[0].min(part.min[0]), min[1].min(part.min[1])],
+                [max[0].max(part.max[0]), max[1].max(part.max[1])],
+            )),
+        })
+    }
+
+    /// Shades the covered pixels between `min` and `max`, handing their
+    /// colors to `set_pixel`.
+    fn rasterize<S, F>(
+        &self,
+        min: [i64; 2],
+        max: [i64; 2],
+        shader: &S,
+        uniforms: &Uniforms,
+        mut set_pixel: F,
+    ) where
+        S: Shader<Varying = V>,
+        F: FnMut(Vector3<f64>, [u8; 3]),
+    {
+        for part in &self.parts {
+            self.rasterize_part(part, min, max, shader, uniforms, &mut set_pixel);
+        }
+    }
+
+    fn rasterize_part<S, F>(
+        &self,
+        setup: &Setup,
+        min: [i64; 2],
+        max: [i64; 2],
+        shader: &S,
+        uniforms: &Uniforms,
+        set_pixel: &mut F,
+    ) where
+        S: Shader<Varying = V>,
+        F: FnMut(Vector3<f64>, [u8; 3]),
+    {
+        let min = [min[0].max(setup.min[0]), min[1].max(setup.min[1])];
+        let max = [max[0].min(setup.max[0]), max[1].min(setup.max[1])];
+        if min[0] > max[0] || min[1] > max[1] {
+            return;
         }
 
-        let coord = coords[0] * barycenter.x + coords[1] * barycenter.y + coords[2] * barycenter.z;
+        let edges = &setup.edges;
+        let area = setup.area as f64;
+        let mut row = [
+            edges[0].at(min[0], min[1]),
+            edges[1].at(min[0], min[1]),
+            edges[2].at(min[0], min[1]),
+        ];
+        for y in min[1]..=max[1] {
+            let mut values = row;
+            for x in min[0]..=max[0] {
+                if values[0] >= edges[0].bias
+                    && values[1] >= edges[1].bias
+                    && values[2] >= edges[2].bias
+                {
+                    let mut barycenter = Vector3::new(
+                        values[0] as f64 / area,
+                        values[1] as f64 / area,
+                        values[2] as f64 / area,
+                    );
+                    if let Some(corners) = &setup.corners {
+                        barycenter = corners[0] * barycenter.x
+                            + corners[1] * barycenter.y
+                            + corners[2] * barycenter.z;
+                    }
+                    let z =
+                        self.a.z * barycenter.x + self.b.z * barycenter.y + self.c.z * barycenter.z;
+
+                    let pixel: Vector3<f64> = Vector3::new(x as f64, y as f64, z);
 
-        let x = coord.x.round() as u32;
-        let y = coord.y.round() as u32;
+                    let fragment = Fragment {
+                        position: pixel,
+                        barycentric: barycenter,
+                        varying: self.varying(barycenter),
+                    };
 
-        if self.texture.in_bounds(x, y) {
-            let pixel = self.texture.get_pixel(x, y);
-            debug!("Pixel: {:#?}", pixel);
-            debug!("Pixel R, G, B: {}, {}, {}", pixel[0], pixel[1], pixel[2]);
-            return [pixel[0], pixel[1], pixel[2]];
+                    if let Some(color) = shader.fragment(uniforms, &fragment) {
+                        set_pixel(pixel, color);
+                    }
+                }
+                for (value, edge) in values.iter_mut().zip(edges) {
+                    *value += edge.step[0];
+                }
+            }
+            for (value, edge) in row.iter_mut().zip(edges) {
+                *value += edge.step[1];
+            }
         }
-        warn!(
-            "Requested color outside texture bounds: {}, {}, {}, {}",
-            x,
-            y,
-            self.texture.width(),
-            self.texture.height(),
-        );
-        [255, 255, 255]
     }
+}
 
-    fn barycentric(&self, vertex: Vector3<f64>) -> Vector3<f64> {
-        let u: Vector3<f64> = Vector3::new(
-            self.c.x - self.a.x,
-            self.b.x - self.a.x,
-            self.a.x - vertex.x,
-        )
-        .cross(Vector3::new(
-            self.c.y - self.a.y,
-            self.b.y - self.a.y,
-            self.a.y - vertex.y,
-        ));
-
-        if u.z.abs() < 1. {
-            return Vector3::new(-1., 1., 1.);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::shader::SolidShader;
+    use crate::render::Scene;
+
+    /// Counts how many times each pixel is drawn, and keeps its last depth.
+    struct Coverage {
+        width: u32,
+        height: u32,
+        counts: Vec<u32>,
+        depths: Vec<f64>,
+    }
+
+    impl Renderer for Coverage {
+        fn new(width: u32, height: u32) -> Coverage {
+            Coverage {
+                width,
+                height,
+                counts: vec![0; (width * height) as usize],
+                depths: vec![0.; (width * height) as usize],
+            }
         }
 
-        Vector3::new(1. - (u.x + u.y) / u.z, u.y / u.z, u.x / u.z)
+        fn get_size(&self) -> (u32, u32) {
+            (self.width, self.height)
+        }
+
+        fn set_pixel(&mut self, pixel: Vector3<f64>, _: [u8; 3]) {
+            let index = (pixel.x as u32 + pixel.y as u32 * self.width) as usize;
+            self.counts[index] += 1;
+            self.depths[index] = pixel.z;
+        }
+
+        fn render(&mut self) -> Result<()> {
+            Ok(())
+        }
     }
 
-    pub fn render(&self, renderer: &mut impl Renderer) -> Result<bool, Box<error::Error>> {
-        let (min, max) = minmax(&[self.a, self.b, self.c]);
+    fn draw(coverage: &mut Coverage, corners: [[f64; 3]; 3]) {
+        let scene = Scene::default();
+        let uniforms = Uniforms::new(&scene, coverage.width, coverage.height);
+        let [a, b, c] = corners;
+        Triangle::new(a.into(), b.into(), c.into(), [(); 3])
+            .unwrap()
+            .render(coverage, &SolidShader { color: [255; 3] }, &uniforms)
+            .unwrap();
+    }
 
-        for x in min.x.round() as u32..=max.x.round() as u32 {
-            for y in min.y.round() as u32..=max.y.round() as u32 {
-                let vertex = Vector3::new(f64::from(x), f64::from(y), 0.);
-                let barycenter = self.barycentric(vertex);
-                if barycenter.x < 0. || barycenter.y < 0. || barycenter.z < 0. {
-                    continue;
+    #[test]
+    fn shared_edges() {
+        // A square fanned around its center, in both windings. Its edges and
+        // diagonals run through pixel centers.
+        let corners = [[0., 0., 0.], [8., 0., 0.], [8., 8., 0.], [0., 8., 0.]];
+        let center = [4., 4., 0.];
+        for &clockwise in &[false, true] {
+            let mut coverage = Coverage::new(10, 10);
+            for index in 0..4 {
+                let (start, end) = (corners[index], corners[(index + 1) % 4]);
+                if clockwise {
+                    draw(&mut coverage, [center, end, start]);
+                } else {
+                    draw(&mut coverage, [center, start, end]);
+                }
+            }
+            // Only the left and top edges of the square are drawn.
+            for y in 0..10 {
+                for x in 0..10 {
+                    let expected = if x < 8 && (1..=8).contains(&y) { 1 } else { 0 };
+                    assert_eq!(coverage.counts[x + y * 10], expected, "({}, {})", x, y);
                 }
-                let z = self.a.z * barycenter.x + self.b.z * barycenter.y + self.c.z * barycenter.z;
+            }
+        }
+    }
 
-                let pixel: Vector3<f64> = Vector3::new(f64::from(x), f64::from(y), z);
+    #[test]
+    fn small_triangle() {
+        // Covers the center of pixel (2, 2) with a tenth of a pixel of area.
+        let mut coverage = Coverage::new(4, 4);
+        draw(
+            &mut coverage,
+            [[1.9, 1.9, 0.], [2.3, 1.9, 0.], [2., 2.3, 0.]],
+        );
+        assert_eq!(coverage.counts.iter().sum::<u32>(), 1);
+        assert_eq!(coverage.counts[2 + 2 * 4], 1);
+    }
 
-                let color = render_common::color(self.get_color(barycenter), self.intensity);
+    #[test]
+    fn offscreen_corners() {
+        let mut coverage = Coverage::new(4, 4);
+        draw(
+            &mut coverage,
+            [[-1e3, -1e3, 0.], [1e3, -1e3, 0.], [0., 1e3, 0.]],
+        );
+        assert!(coverage.counts.iter().all(|&count| count == 1));
+    }
 
-                renderer.set_pixel(pixel, color);
+    #[test]
+    fn distant_corners() {
+        // Corners out of range of the fixed point coordinates. The triangle
+        // covers the pixels with x + y >= 3, with depth rising to 1 at its
+        // first corner.
+        let mut coverage = Coverage::new(4, 4);
+        draw(
+            &mut coverage,
+            [[1e7, 1e7, 1.], [-1e7 + 3., 1e7, 0.], [1e7, -1e7 + 3., 0.]],
+        );
+        for y in 0..4 {
+            for x in 0..4 {
+                let index = x + y * 4;
+                let expected = if x + y >= 3 { 1 } else { 0 };
+                assert_eq!(coverage.counts[index], expected, "({}, {})", x, y);
+                if expected == 1 {
+                    let depth = (x + y - 3) as f64 / (2e7 - 3.);
+                    assert!((coverage.depths[index] - depth).abs() < 1e-9);
+                }
             }
         }
-
-        Ok(true)
     }
 }
@@ -1,15 +1,26 @@
+mod cli;
+
 use std::env;
+use std::process;
 
-use env_logger;
+use cpuengine::Error;
 
 fn main() {
     env_logger::init();
 
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = cli::Options::parse(&args).and_then(|options| cli::run(&options));
 
-    if args[1] == "line" {
-        cpuengine::render_line(&args);
-    } else if args[1] == "object" {
-        cpuengine::render_obj(&args);
+    match result {
+        Ok(()) => {}
+        Err(err @ Error::Argument(_)) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
     }
 }
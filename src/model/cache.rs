@@ -0,0 +1,440 @@
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use cgmath::{Vector3, Vector4};
+use log::debug;
+use memmap2::Mmap;
+
+use crate::error::{Error, Result};
+use crate::model::obj::{Face, Group, Object};
+use crate::render::Material;
+
+/// Start of every cache file.
+const MAGIC: &[u8; 8] = b"CPUEOBJ\0";
+/// Version of the cache layout. Bump it whenever the layout changes, or
+/// when `Object::new` fills in objects differently, e.g. generates other
+/// normals, so that existing caches are parsed again.
+const VERSION: u32 = 1;
+
+/// Maps a cache file into memory for `load`.
+fn map(path: &Path) -> Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the map must not change or shrink while it is borrowed, or
+    // reads from it are undefined behaviour (SIGBUS on truncation). Caches
+    // are only ever replaced by renaming a new file over them, which leaves
+    // the mapped file intact, and the map only lives for the duration of
+    // `load`. A process writing into the cache in place breaks this.
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+/// Path of the cache of the OBJ file at `path`, next to it with `.cache`
+/// appended, e.g. `head.obj.cache`.
+pub fn path(source: &Path) -> PathBuf {
+    let mut path = source.as_os_str().to_owned();
+    path.push(".cache");
+    PathBuf::from(path)
+}
+
+/// 64-bit FNV-1a hash of a source file, stored in its cache to tell whether
+/// the source changed since.
+pub(crate) fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Writes `object`, parsed from a file hashing to `source_hash`, to the
+/// cache at `path`. The cache is written to a temporary file first, so an
+/// interrupted write never leaves a truncated cache behind.
+pub(crate) fn save(path: &Path, object: &Object, source_hash: u64) -> Result<()> {
+    debug!("Saving object cache: {}", path.display());
+    let mut bytes = MAGIC.to_vec();
+    let mut encoder = Encoder(&mut bytes);
+    encoder.u32(VERSION);
+    encoder.u64(source_hash);
+
+    encoder.vectors(&object.vertices);
+    encoder.vectors(&object.textures);
+    encoder.vectors(&object.normals);
+    encoder.u64(object.authored_normals as u64);
+    encoder.vectors(&object.colors);
+    encoder.count(object.tangents.len());
+    for tangent in &object.tangents {
+        encoder.f64s(&[tangent.x, tangent.y, tangent.z, tangent.w]);
+    }
+
+    encoder.count(object.faces.len());
+    for (face, triangles) in object.faces.iter().zip(&object.triangles) {
+        encoder.count(face.len());
+        for vertex in face {
+            encoder.u32s(&[vertex.vertex, vertex.texture, vertex.normal, vertex.tangent]);
+        }
+        encoder.count(triangles.len());
+        for triangle in triangles {
+            encoder.u32s(&[triangle[0] as u32, triangle[1] as u32, triangle[2] as u32]);
+        }
+    }
+
+    encoder.count(object.material_libraries.len());
+    for library in &object.material_libraries {
+        encoder.string(library);
+    }
+    encoder.count(object.materials.len());
+    for material in &object.materials {
+        encoder.string(&material.name);
+    }
+    encoder.indices(&object.face_materials);
+    encoder.count(object.smoothing_groups.len());
+    encoder.u32s(&object.smoothing_groups);
+    for groups in &[&object.objects, &object.groups] {
+        encoder.count(groups.len());
+        for group in groups.iter() {
+            encoder.string(&group.name);
+            encoder.indices(&group.faces);
+        }
+    }
+    encoder.count(object.lines.len());
+    for line in &object.lines {
+        encoder.count(line.len());
+        encoder.u32s(line);
+    }
+    encoder.count(object.points.len());
+    encoder.u32s(&object.points);
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, &bytes)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Loads the object cached at `path`, with materials from its libraries
+/// looked up by name through `load_library`, which is given each library
+/// named by the cached `mtllib` statements. Returns `None` if there is no
+/// cache, or it was written by another version or for another source.
+pub(crate) fn load<F>(path: &Path, source_hash: u64, mut load_library: F) -> Result<Option<Object>>
+where
+    F: FnMut(&str) -> Result<Vec<Material>>,
+{
+    let bytes = match map(path) {
+        Ok(bytes) => bytes,
+        Err(Error::Io(_)) => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let mut decoder = Decoder {
+        path,
+        bytes: &bytes,
+    };
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(Error::format(path, "not an object cache"));
+    }
+    let version = decoder.u32()?;
+    if version != VERSION {
+        debug!(
+            "Object cache {} has version {}, expected {}",
+            path.display(),
+            version,
+            VERSION
+        );
+        return Ok(None);
+    }
+    if decoder.u64()? != source_hash {
+        debug!("Object cache {} is out of date", path.display());
+        return Ok(None);
+    }
+    debug!("Loading object cache: {}", path.display());
+
+    let vertices = decoder.vectors()?;
+    let textures = decoder.vectors()?;
+    let normals = decoder.vectors()?;
+    let authored_normals = decoder.u64()? as usize;
+    let colors = decoder.vectors()?;
+    let tangents = decoder.list(32, |decoder| {
+        Ok(Vector4::new(
+            decoder.f64()?,
+            decoder.f64()?,
+            decoder.f64()?,
+            decoder.f64()?,
+        ))
+    })?;
+
+    let count = decoder.count(8)?;
+    let mut faces = Vec::with_capacity(count);
+    let mut triangles = Vec::with_capacity(count);
+    for _ in 0..count {
+        let face = decoder.list(16, |decoder| {
+            Ok(Face {
+                vertex: decoder.u32()?,
+                texture: decoder.u32()?,
+                normal: decoder.u32()?,
+                tangent: decoder.u32()?,
+            })
+        })?;
+        let corners = face.len();
+        triangles.push(decoder.list(12, |decoder| {
+            let mut triangle = [0; 3];
+            for corner in &mut triangle {
+                *corner = decoder.index(corners)?;
+            }
+            Ok(triangle)
+        })?);
+        faces.push(face);
+    }
+
+    let material_libraries = decoder.list(8, Decoder::string)?;
+    let mut library_materials = Vec::new();
+    for library in &material_libraries {
+        library_materials.extend(load_library(library)?);
+    }
+    let materials = decoder.list(8, |decoder| {
+        let name = decoder.string()?;
+        Ok(library_materials
+            .iter()
+            .find(|material| material.name == name)
+            .cloned()
+            .unwrap_or_else(|| Material::new(name)))
+    })?;
+    let face_materials = decoder.list(4, |decoder| decoder.index(materials.len()))?;
+    let smoothing_groups = decoder.list(4, Decoder::u32)?;
+    let objects = decoder.list(8, |decoder| decoder.group(faces.len()))?;
+    let groups = decoder.list(8, |decoder| decoder.group(faces.len()))?;
+    let lines = decoder.list(8, |decoder| decoder.list(4, Decoder::u32))?;
+    let points = decoder.list(4, Decoder::u32)?;
+    if !decoder.bytes.is_empty() {
+        return Err(Error::format(path, "trailing data in object cache"));
+    }
+
+    let object = Object {
+        visible: vec![true; faces.len()],
+        faces,
+        triangles,
+        vertices,
+        normals,
+        authored_normals,
+        textures,
+        colors,
+        tangents,
+        material_libraries,
+        materials,
+        face_materials,
+        smoothing_groups,
+        objects,
+        groups,
+        lines,
+        points,
+    };
+    check(path, &object)?;
+    Ok(Some(object))
+}
+
+/// Checks that the indices of a cached object are in range, so that a
+/// corrupt cache is reported instead of failing later.
+fn check(path: &Path, object: &Object) -> Result<()> {
+    let in_range = |index: u32, len: usize| index as usize <= len;
+    let faces_valid = object.faces.iter().flatten().all(|vertex| {
+        vertex.vertex >= 1
+            && in_range(vertex.vertex, object.vertices.len())
+            && in_range(vertex.texture, object.textures.len())
+            && in_range(vertex.normal, object.normals.len())
+            && in_range(vertex.tangent, object.tangents.len())
+    });
+    let lines_valid = object
+        .lines
+        .iter()
+        .flatten()
+        .chain(&object.points)
+        .all(|index| *index >= 1 && in_range(*index, object.vertices.len()));
+    let lengths_valid = object.smoothing_groups.len() == object.faces.len()
+        && object.face_materials.len() == object.faces.len()
+        && object.authored_normals <= object.normals.len()
+        && (object.colors.is_empty() || object.colors.len() == object.vertices.len());
+    if faces_valid && lines_valid && lengths_valid {
+        Ok(())
+    } else {
+        Err(Error::format(path, "object cache has inconsistent indices"))
+    }
+}
+
+/// Appends little-endian values to a cache.
+struct Encoder<'a>(&'a mut Vec<u8>);
+
+impl<'a> Encoder<'a> {
+    fn u32(&mut self, value: u32) {
+        self.0.extend(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend(&value.to_le_bytes());
+    }
+
+    fn count(&mut self, count: usize) {
+        self.u64(count as u64);
+    }
+
+    fn u32s(&mut self, values: &[u32]) {
+        for value in values {
+            self.u32(*value);
+        }
+    }
+
+    fn f64s(&mut self, values: &[f64]) {
+        for value in values {
+            self.0.extend(&value.to_le_bytes());
+        }
+    }
+
+    fn indices(&mut self, indices: &[usize]) {
+        self.count(indices.len());
+        for index in indices {
+            self.u32(*index as u32);
+        }
+    }
+
+    fn vectors(&mut self, vectors: &[Vector3<f64>]) {
+        self.count(vectors.len());
+        for vector in vectors {
+            self.f64s(&[vector.x, vector.y, vector.z]);
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.count(string.len());
+        self.0.extend(string.as_bytes());
+    }
+}
+
+/// Reads the values written by `Encoder`, failing on truncated data.
+struct Decoder<'a> {
+    path: &'a Path,
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < size {
+            return Err(Error::format(self.path, "truncated object cache"));
+        }
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads the length of a list of items at least `size` bytes each,
+    /// checking that they fit in the rest of the cache before anything is
+    /// allocated for them.
+    fn count(&mut self, size: usize) -> Result<usize> {
+        let count = self.u64()?;
+        if count > (self.bytes.len() / size) as u64 {
+            return Err(Error::format(self.path, "truncated object cache"));
+        }
+        Ok(count as usize)
+    }
+
+    /// Reads an index, checking that it is less than `len`.
+    fn index(&mut self, len: usize) -> Result<usize> {
+        let index = self.u32()? as usize;
+        if index >= len {
+            return Err(Error::format(
+                self.path,
+                "object cache has inconsistent indices",
+            ));
+        }
+        Ok(index)
+    }
+
+    fn list<T, F>(&mut self, size: usize, mut item: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&mut Decoder<'a>) -> Result<T>,
+    {
+        let count = self.count(size)?;
+        (0..count).map(|_| item(self)).collect()
+    }
+
+    fn vectors(&mut self) -> Result<Vec<Vector3<f64>>> {
+        self.list(24, |decoder| {
+            Ok(Vector3::new(decoder.f64()?, decoder.f64()?, decoder.f64()?))
+        })
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.count(1)?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::format(self.path, "object cache holds invalid UTF-8"))
+    }
+
+    fn group(&mut self, faces: usize) -> Result<Group> {
+        Ok(Group {
+            name: self.string()?,
+            faces: self.list(4, |decoder| decoder.index(faces))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_cached() {
+        let directory =
+            std::env::temp_dir().join(format!("cpuengine-object-cache-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("colors.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let source = directory.join("quad.obj");
+        fs::write(
+            &source,
+            "mtllib colors.mtl\nv 0 0 0 1 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+             o quad\ng a b\nusemtl red\nf 1/1/1 2/1/1 3/1/1 4/1/1\ns 1\nusemtl none\nf 1 2 3\n\
+             l 1 2 3\np 4\n",
+        )
+        .unwrap();
+
+        let parsed = Object::load_cached(&source).unwrap();
+        let cached = Object::load_cached(&source).unwrap();
+        assert!(path(&source).is_file());
+        assert_eq!(cached.vertices, parsed.vertices);
+        assert_eq!(cached.normals, parsed.normals);
+        assert_eq!(cached.authored_normals, 1);
+        assert_eq!(cached.colors, parsed.colors);
+        assert_eq!(cached.tangents, parsed.tangents);
+        assert_eq!(cached.faces.len(), 2);
+        assert_eq!(cached.faces[1][2].normal, parsed.faces[1][2].normal);
+        assert_eq!(cached.triangles, parsed.triangles);
+        assert_eq!(cached.material_libraries, vec!["colors.mtl"]);
+        assert_eq!(cached.materials.len(), 2);
+        assert_eq!(cached.materials[0].diffuse, Vector3::new(1., 0., 0.));
+        assert_eq!(cached.face_materials, vec![0, 1]);
+        assert_eq!(cached.smoothing_groups, vec![0, 1]);
+        assert_eq!(cached.objects, parsed.objects);
+        assert_eq!(cached.groups, parsed.groups);
+        assert_eq!(cached.lines, vec![vec![1, 2, 3]]);
+        assert_eq!(cached.points, vec![4]);
+
+        // Caches of other contents are ignored, and broken ones reported.
+        let hash = hash(&fs::read(&source).unwrap());
+        assert!(load(&path(&source), hash + 1, |_| Ok(Vec::new()))
+            .unwrap()
+            .is_none());
+        let bytes = fs::read(path(&source)).unwrap();
+        fs::write(path(&source), &bytes[..bytes.len() - 1]).unwrap();
+        match load(&path(&source), hash, |_| Ok(Vec::new())) {
+            Err(Error::Format { .. }) => {}
+            _ => panic!("expected a format error"),
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
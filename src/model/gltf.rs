@@ -0,0 +1,901 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use cgmath::{InnerSpace, Matrix, Matrix4, Quaternion, SquareMatrix, Vector3, Vector4};
+use image::{load_from_memory, ColorType, DynamicImage, GenericImageView, GrayImage, Luma};
+use log::{debug, warn};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::model::mesh::Mesh;
+use crate::render::common::{NormalMap, NormalSpace, SpecularMap, Wrap};
+use crate::render::Material;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+/// Primitive modes, see the `mode` property of a mesh primitive.
+const POINTS: u64 = 0;
+const LINES: u64 = 1;
+const LINE_LOOP: u64 = 2;
+const LINE_STRIP: u64 = 3;
+const TRIANGLES: u64 = 4;
+const TRIANGLE_STRIP: u64 = 5;
+const TRIANGLE_FAN: u64 = 6;
+
+/// Most elements an accessor without a buffer view may have. Their zeros
+/// take no space in the file, so a malformed count could otherwise ask for
+/// any amount of memory.
+const MAX_ZEROED_COUNT: usize = 1 << 20;
+
+/// Loads a glTF 2.0 asset, either a `.gltf` file with its buffers and images
+/// embedded as data URIs or next to it, or a binary `.glb`. The meshes of the
+/// default scene are flattened into one mesh with their node transforms
+/// applied.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    debug!("Loading glTF: {}", path.display());
+    let file_contents = fs::read(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(path, &file_contents, directory)
+}
+
+pub(crate) fn parse(path: &Path, file_contents: &[u8], directory: &Path) -> Result<Mesh> {
+    let (json, binary) = if file_contents.starts_with(GLB_MAGIC) {
+        split_glb(path, file_contents)?
+    } else {
+        (file_contents, None)
+    };
+    let json: Value = serde_json::from_slice(json)
+        .map_err(|error| Error::format(path, format!("invalid JSON: {}", error)))?;
+    let version = json["asset"]["version"].as_str().unwrap_or_default();
+    if !version.starts_with("2.") {
+        return Err(Error::format(
+            path,
+            format!("unsupported glTF version `{}`", version),
+        ));
+    }
+
+    let mut document = Document {
+        path,
+        directory,
+        json: &json,
+        buffers: Vec::new(),
+        images: HashMap::new(),
+    };
+    document.buffers = array(&json, "buffers")
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| document.buffer(index, buffer, binary))
+        .collect::<Result<_>>()?;
+    document.mesh()
+}
+
+/// Splits a binary glTF file into its JSON and binary chunks.
+fn split_glb<'a>(path: &Path, file_contents: &'a [u8]) -> Result<(&'a [u8], Option<&'a [u8]>)> {
+    let word = |offset: usize| -> Result<u32> {
+        file_contents
+            .get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or_else(|| Error::format(path, "truncated GLB file"))
+    };
+    if word(4)? != 2 {
+        return Err(Error::format(path, "unsupported GLB version"));
+    }
+    let length = (word(8)? as usize).min(file_contents.len());
+
+    let mut offset = 12;
+    let (mut json, mut binary) = (None, None);
+    while offset + 8 <= length {
+        let (size, kind) = (word(offset)? as usize, word(offset + 4)?);
+        let chunk = file_contents
+            .get(offset + 8..offset + 8 + size)
+            .ok_or_else(|| Error::format(path, "truncated GLB chunk"))?;
+        match kind {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if binary.is_none() => binary = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + size;
+    }
+    let json = json.ok_or_else(|| Error::format(path, "GLB file has no JSON chunk"))?;
+    Ok((json, binary))
+}
+
+/// Elements of the top level array `name`, empty if it is missing.
+fn array<'a>(json: &'a Value, name: &str) -> &'a [Value] {
+    json[name].as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// A number array of the expected length, or `default`.
+fn numbers(value: &Value, default: &[f64]) -> Vec<f64> {
+    match value.as_array() {
+        Some(values) if values.len() == default.len() => values
+            .iter()
+            .zip(default)
+            .map(|(value, default)| value.as_f64().unwrap_or(*default))
+            .collect(),
+        _ => default.to_vec(),
+    }
+}
+
+struct Document<'a> {
+    path: &'a Path,
+    directory: &'a Path,
+    json: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    /// Decoded images by index, `None` for those that failed to load.
+    images: HashMap<usize, Option<DynamicImage>>,
+}
+
+impl<'a> Document<'a> {
+    fn error<M: Into<String>>(&self, message: M) -> Error {
+        Error::format(self.path, message)
+    }
+
+    /// The element at `index` of the top level array `collection`.
+    fn get(&self, collection: &'static str, index: u64) -> Result<&'a Value> {
+        let elements = array(self.json, collection);
+        elements.get(index as usize).ok_or(Error::InvalidIndex {
+            kind: collection,
+            index: index as i64,
+            len: elements.len(),
+        })
+    }
+
+    /// Reads a data URI, or a file relative to the asset.
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>> {
+        if uri.starts_with("data:") {
+            let (header, data) = match uri.find(',') {
+                Some(comma) => (&uri[..comma], &uri[comma + 1..]),
+                None => return Err(self.error("malformed data URI")),
+            };
+            if !header.ends_with(";base64") {
+                return Err(self.error("only base64 data URIs are supported"));
+            }
+            return decode_base64(data).ok_or_else(|| self.error("invalid base64 in data URI"));
+        }
+        Ok(fs::read(self.directory.join(percent_decode(uri)))?)
+    }
+
+    fn buffer(&self, index: usize, buffer: &Value, binary: Option<&[u8]>) -> Result<Vec<u8>> {
+        let data = match buffer["uri"].as_str() {
+            Some(uri) => self.resolve(uri)?,
+            None => binary
+                .ok_or_else(|| self.error(format!("buffer {} has no data", index)))?
+                .to_vec(),
+        };
+        let length = buffer["byteLength"].as_u64().unwrap_or(0) as usize;
+        if data.len() < length {
+            return Err(self.error(format!(
+                "buffer {} holds {} bytes, expected {}",
+                index,
+                data.len(),
+                length
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Bytes of a buffer view and the stride between its elements, if set.
+    fn buffer_view(&self, index: u64) -> Result<(&[u8], Option<usize>)> {
+        let view = self.get("bufferViews", index)?;
+        let buffer = view["buffer"].as_u64().unwrap_or(0) as usize;
+        let buffer = self.buffers.get(buffer).ok_or(Error::InvalidIndex {
+            kind: "buffers",
+            index: buffer as i64,
+            len: self.buffers.len(),
+        })?;
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = view["byteLength"].as_u64().unwrap_or(0) as usize;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| self.error(format!("buffer view {} is out of bounds", index)))?;
+        let stride = view["byteStride"].as_u64().map(|stride| stride as usize);
+        Ok((data, stride))
+    }
+
+    /// Reads the elements of an accessor, padding vectors with zeros.
+    fn accessor(&self, index: u64) -> Result<Vec<[f64; 4]>> {
+        let accessor = self.get("accessors", index)?;
+        if accessor.get("sparse").is_some() {
+            return Err(self.error("sparse accessors are not supported"));
+        }
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            kind => {
+                return Err(self.error(format!(
+                    "unsupported accessor type `{}`",
+                    kind.unwrap_or_default()
+                )))
+            }
+        };
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(self.error(format!("unsupported component type {}", component_type))),
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+
+        let view = match accessor["bufferView"].as_u64() {
+            Some(view) => view,
+            // Accessors without a buffer view are all zeros.
+            None if count <= MAX_ZEROED_COUNT => return Ok(vec![[0.; 4]; count]),
+            None => {
+                return Err(self.error(format!(
+                    "accessor {} has {} elements but no buffer view",
+                    index, count
+                )))
+            }
+        };
+        let (data, stride) = self.buffer_view(view)?;
+        let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let stride = stride.unwrap_or(size * components);
+        // Overlapping elements would let a bogus count pass the bounds check.
+        if stride < size * components {
+            return Err(self.error(format!(
+                "accessor {} has elements of {} bytes but a stride of {}",
+                index,
+                size * components,
+                stride
+            )));
+        }
+        // Check the last element is in bounds before allocating anything, so
+        // a bogus count is an error rather than a failed allocation.
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| start.checked_add(components * size)),
+            None => Some(0),
+        };
+        if !matches!(end, Some(end) if end <= data.len()) {
+            return Err(self.error(format!("accessor {} reads past its buffer view", index)));
+        }
+
+        let mut values = vec![[0.; 4]; count];
+        for (element, value) in values.iter_mut().enumerate() {
+            for (component, value) in value.iter_mut().enumerate().take(components) {
+                let start = offset + element * stride + component * size;
+                *value = decode_component(component_type, &data[start..start + size], normalized);
+            }
+        }
+        Ok(values)
+    }
+
+    /// The image of a texture, decoded and flipped to match the texture
+    /// coordinates. Images that cannot be loaded are reported and skipped.
+    fn texture(&mut self, texture: &Value) -> Option<DynamicImage> {
+        let index = texture["index"].as_u64()?;
+        if texture["texCoord"].as_u64().unwrap_or(0) != 0 {
+            warn!(
+                "Only TEXCOORD_0 is supported, using it for texture {}",
+                index
+            );
+        }
+        let source = match self.get("textures", index) {
+            Ok(texture) => texture["source"].as_u64()?,
+            Err(error) => {
+                warn!("Skipping texture: {}", error);
+                return None;
+            }
+        };
+        if !self.images.contains_key(&(source as usize)) {
+            let image = match self.image(source) {
+                Ok(image) => Some(image.flipv()),
+                Err(error) => {
+                    warn!("Skipping image {}: {}", source, error);
+                    None
+                }
+            };
+            self.images.insert(source as usize, image);
+        }
+        self.images[&(source as usize)].clone()
+    }
+
+    /// Wrapping of a texture along `u` and `v`, from its sampler.
+    fn wrap(&self, texture: &Value) -> [Wrap; 2] {
+        let sampler = texture["index"]
+            .as_u64()
+            .and_then(|index| self.get("textures", index).ok())
+            .and_then(|texture| texture["sampler"].as_u64())
+            .and_then(|sampler| self.get("samplers", sampler).ok());
+        let wrap = |mode: &str| match sampler.and_then(|sampler| sampler[mode].as_u64()) {
+            Some(33071) => Wrap::Clamp,
+            Some(33648) => Wrap::MirroredRepeat,
+            _ => Wrap::Repeat,
+        };
+        [wrap("wrapS"), wrap("wrapT")]
+    }
+
+    fn image(&self, index: u64) -> Result<DynamicImage> {
+        let image = self.get("images", index)?;
+        let bytes = match (image["uri"].as_str(), image["bufferView"].as_u64()) {
+            (Some(uri), _) => self.resolve(uri)?,
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            (None, None) => return Err(self.error(format!("image {} has no data", index))),
+        };
+        Ok(load_from_memory(&bytes)?)
+    }
+
+    /// Approximates a metallic-roughness material with the Blinn-Phong
+    /// parameters of `Material`. Metals keep their base color as diffuse
+    /// color, since there is no environment for them to reflect.
+    fn material(&mut self, material: &Value) -> Material {
+        let mut result = Material::new(String::from(material["name"].as_str().unwrap_or_default()));
+        let pbr = &material["pbrMetallicRoughness"];
+        let base = numbers(&pbr["baseColorFactor"], &[1., 1., 1., 1.]);
+        let metallic = pbr["metallicFactor"].as_f64().unwrap_or(1.);
+        let roughness = pbr["roughnessFactor"].as_f64().unwrap_or(1.);
+        result.diffuse = Vector3::new(base[0], base[1], base[2]);
+        let dielectric = Vector3::new(0.04, 0.04, 0.04);
+        result.specular = dielectric + (result.diffuse - dielectric) * metallic;
+        // Exponent of the Blinn-Phong lobe closest to a GGX one.
+        let alpha = (roughness * roughness).max(1e-3);
+        result.shininess = (2. / (alpha * alpha) - 2.).clamp(1., 1024.);
+
+        let blended = matches!(material["alphaMode"].as_str(), Some("MASK") | Some("BLEND"));
+        if blended {
+            result.opacity = base[3];
+        }
+        // Materials wrap all of their maps alike, so the first texture's
+        // sampler is used.
+        if let Some(texture) = [
+            &pbr["baseColorTexture"],
+            &pbr["metallicRoughnessTexture"],
+            &material["normalTexture"],
+        ]
+        .iter()
+        .find(|texture| texture["index"].is_u64())
+        {
+            result.wrap = self.wrap(texture);
+        }
+        if let Some(texture) = self.texture(&pbr["baseColorTexture"]) {
+            let has_alpha = matches!(
+                texture.color(),
+                ColorType::GrayA(_) | ColorType::RGBA(_) | ColorType::BGRA(_)
+            );
+            if blended && has_alpha {
+                let rgba = texture.to_rgba();
+                result.alpha_map = Some(DynamicImage::ImageLuma8(GrayImage::from_fn(
+                    rgba.width(),
+                    rgba.height(),
+                    |x, y| Luma([rgba.get_pixel(x, y)[3]]),
+                )));
+            }
+            result.diffuse_map = Some(texture);
+        }
+        if let Some(texture) = self.texture(&pbr["metallicRoughnessTexture"]) {
+            // Roughness is in the green channel, and smooth texels get the
+            // full highlight.
+            let (width, height) = texture.dimensions();
+            let rgb = texture.to_rgb();
+            result.specular_map = Some(SpecularMap {
+                image: DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                    Luma([255 - rgb.get_pixel(x, y)[1]])
+                })),
+            });
+        }
+        if let Some(image) = self.texture(&material["normalTexture"]) {
+            result.normal_map = Some(NormalMap {
+                image,
+                space: NormalSpace::Tangent,
+            });
+        }
+        result
+    }
+
+    fn mesh(&mut self) -> Result<Mesh> {
+        let mut mesh = Mesh::default();
+        let materials: Vec<Value> = array(self.json, "materials").to_vec();
+        mesh.materials = materials
+            .iter()
+            .map(|material| self.material(material))
+            .collect();
+        let mut attributes = Attributes::default();
+
+        let nodes = array(self.json, "nodes");
+        let roots: Vec<u64> = match self.json["scenes"].as_array() {
+            Some(scenes) if !scenes.is_empty() => {
+                let scene = self.json["scene"].as_u64().unwrap_or(0);
+                numbers_u64(&self.get("scenes", scene)?["nodes"])
+            }
+            // Without scenes, draw every node that is not a child of another.
+            _ => {
+                let children: Vec<u64> = nodes
+                    .iter()
+                    .flat_map(|node| numbers_u64(&node["children"]))
+                    .collect();
+                (0..nodes.len() as u64)
+                    .filter(|node| !children.contains(node))
+                    .collect()
+            }
+        };
+        for root in roots {
+            self.node(root, Matrix4::identity(), 0, &mut mesh, &mut attributes)?;
+        }
+
+        if !attributes.normals {
+            mesh.normals.clear();
+        } else {
+            fill_normals(&mut mesh, &attributes.missing_normals);
+        }
+        if !attributes.textures {
+            mesh.textures.clear();
+        }
+        if !attributes.colors {
+            mesh.colors.clear();
+        }
+        let normal_mapped = mesh.materials.iter().any(|m| m.normal_map.is_some());
+        if !attributes.tangents || attributes.missing_tangents {
+            mesh.tangents.clear();
+            if normal_mapped {
+                mesh.generate_tangents();
+            }
+        }
+        Ok(mesh)
+    }
+
+    fn node(
+        &mut self,
+        index: u64,
+        parent: Matrix4<f64>,
+        depth: usize,
+        mesh: &mut Mesh,
+        attributes: &mut Attributes,
+    ) -> Result<()> {
+        if depth > array(self.json, "nodes").len() {
+            return Err(self.error("the node hierarchy has a cycle"));
+        }
+        let node = self.get("nodes", index)?;
+        let transform = parent * node_transform(node);
+        if let Some(index) = node["mesh"].as_u64() {
+            for primitive in array(self.get("meshes", index)?, "primitives") {
+                self.primitive(primitive, transform, mesh, attributes)?;
+            }
+        }
+        for child in numbers_u64(&node["children"]) {
+            self.node(child, transform, depth + 1, mesh, attributes)?;
+        }
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        primitive: &Value,
+        transform: Matrix4<f64>,
+        mesh: &mut Mesh,
+        attributes: &mut Attributes,
+    ) -> Result<()> {
+        let attribute = |name: &str| primitive["attributes"][name].as_u64();
+        let positions = match attribute("POSITION") {
+            Some(accessor) => self.accessor(accessor)?,
+            None => return Ok(()),
+        };
+        let base = mesh.positions.len() as u32;
+        let count = positions.len();
+        let normal_matrix = transform
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+            .transpose();
+
+        for position in &positions {
+            let position = transform * Vector4::new(position[0], position[1], position[2], 1.);
+            mesh.positions.push(position.truncate() / position.w);
+        }
+        match attribute("NORMAL") {
+            Some(accessor) => {
+                attributes.normals = true;
+                for normal in self.accessor(accessor)?.iter().take(count) {
+                    let normal = normal_matrix * Vector4::new(normal[0], normal[1], normal[2], 0.);
+                    mesh.normals.push(normal.truncate().normalize());
+                }
+            }
+            None => attributes.missing_normals.extend(base..base + count as u32),
+        }
+        if let Some(accessor) = attribute("TEXCOORD_0") {
+            attributes.textures = true;
+            // glTF textures start at the top, ours at the bottom.
+            for coord in self.accessor(accessor)?.iter().take(count) {
+                mesh.textures
+                    .push(Vector3::new(coord[0], 1. - coord[1], 0.));
+            }
+        }
+        if let Some(accessor) = attribute("COLOR_0") {
+            attributes.colors = true;
+            for color in self.accessor(accessor)?.iter().take(count) {
+                mesh.colors.push(Vector3::new(color[0], color[1], color[2]));
+            }
+        }
+        match attribute("TANGENT") {
+            Some(accessor) => {
+                attributes.tangents = true;
+                for tangent in self.accessor(accessor)?.iter().take(count) {
+                    let direction = (transform
+                        * Vector4::new(tangent[0], tangent[1], tangent[2], 0.))
+                    .truncate();
+                    // glTF bitangents are flipped relative to ours, since
+                    // `v` points down its textures.
+                    mesh.tangents
+                        .push(direction.normalize().extend(-tangent[3]));
+                }
+            }
+            None => attributes.missing_tangents = true,
+        }
+        // Pad the attributes this primitive lacks, so that all of them stay
+        // in step with the positions.
+        let length = mesh.positions.len();
+        mesh.normals.resize(length, Vector3::unit_z());
+        mesh.textures.resize(length, Vector3::new(0., 0., 0.));
+        mesh.colors.resize(length, Vector3::new(1., 1., 1.));
+        mesh.tangents.resize(length, Vector4::new(1., 0., 0., 1.));
+
+        let indices: Vec<u32> = match primitive["indices"].as_u64() {
+            Some(accessor) => self
+                .accessor(accessor)?
+                .iter()
+                .map(|index| {
+                    let index = index[0] as u32;
+                    if index as usize >= count {
+                        return Err(Error::InvalidIndex {
+                            kind: "vertex",
+                            index: i64::from(index),
+                            len: count,
+                        });
+                    }
+                    Ok(base + index)
+                })
+                .collect::<Result<_>>()?,
+            None => (base..base + count as u32).collect(),
+        };
+        let material = match primitive["material"].as_u64() {
+            Some(material) if (material as usize) < mesh.materials.len() => material as usize,
+            _ => {
+                if attributes.default_material.is_none() {
+                    mesh.materials.push(Material::default());
+                    attributes.default_material = Some(mesh.materials.len() - 1);
+                }
+                attributes.default_material.unwrap()
+            }
+        };
+        // Mirroring transforms turn the winding around.
+        let mirrored = transform.determinant() < 0.;
+        let add = |mesh: &mut Mesh, [a, b, c]: [u32; 3]| {
+            mesh.triangles
+                .push(if mirrored { [a, c, b] } else { [a, b, c] });
+            mesh.triangle_materials.push(material);
+        };
+
+        match primitive["mode"].as_u64().unwrap_or(TRIANGLES) {
+            TRIANGLES => {
+                for triangle in indices.chunks_exact(3) {
+                    add(mesh, [triangle[0], triangle[1], triangle[2]]);
+                }
+            }
+            TRIANGLE_STRIP => {
+                for (index, triangle) in indices.windows(3).enumerate() {
+                    if index % 2 == 0 {
+                        add(mesh, [triangle[0], triangle[1], triangle[2]]);
+                    } else {
+                        add(mesh, [triangle[1], triangle[0], triangle[2]]);
+                    }
+                }
+            }
+            TRIANGLE_FAN => {
+                for index in 1..indices.len().saturating_sub(1) {
+                    add(mesh, [indices[0], indices[index], indices[index + 1]]);
+                }
+            }
+            LINES => mesh
+                .lines
+                .extend(indices.chunks_exact(2).map(|line| [line[0], line[1]])),
+            LINE_STRIP | LINE_LOOP => {
+                mesh.lines
+                    .extend(indices.windows(2).map(|line| [line[0], line[1]]));
+                if primitive["mode"].as_u64() == Some(LINE_LOOP) && indices.len() > 2 {
+                    mesh.lines.push([indices[indices.len() - 1], indices[0]]);
+                }
+            }
+            POINTS => mesh.points.extend(indices),
+            mode => warn!("Skipping primitive with unknown mode {}", mode),
+        }
+        Ok(())
+    }
+}
+
+/// Which vertex attributes the primitives provided.
+#[derive(Default)]
+struct Attributes {
+    normals: bool,
+    textures: bool,
+    colors: bool,
+    tangents: bool,
+    missing_tangents: bool,
+    /// Vertices of primitives without normals.
+    missing_normals: Vec<u32>,
+    /// Material added for primitives without one.
+    default_material: Option<usize>,
+}
+
+/// Replaces the normals of `vertices` with the area weighted average of the
+/// triangles around them.
+fn fill_normals(mesh: &mut Mesh, vertices: &[u32]) {
+    if vertices.is_empty() {
+        return;
+    }
+    for vertex in vertices {
+        mesh.normals[*vertex as usize] = Vector3::new(0., 0., 0.);
+    }
+    let missing: std::collections::HashSet<u32> = vertices.iter().cloned().collect();
+    for triangle in &mesh.triangles {
+        let position = |index: usize| mesh.positions[triangle[index] as usize];
+        let normal = (position(1) - position(0)).cross(position(2) - position(0));
+        for vertex in triangle.iter().filter(|vertex| missing.contains(vertex)) {
+            mesh.normals[*vertex as usize] += normal;
+        }
+    }
+    for vertex in vertices {
+        let normal = &mut mesh.normals[*vertex as usize];
+        *normal = if normal.magnitude2() > f64::EPSILON {
+            normal.normalize()
+        } else {
+            Vector3::unit_z()
+        };
+    }
+}
+
+fn numbers_u64(value: &Value) -> Vec<u64> {
+    value
+        .as_array()
+        .map(|values| values.iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default()
+}
+
+/// Local transform of a node, from its `matrix` or its translation,
+/// rotation and scale.
+fn node_transform(node: &Value) -> Matrix4<f64> {
+    if let Some(matrix) = node["matrix"].as_array() {
+        if matrix.len() == 16 {
+            let value = |index: usize| matrix[index].as_f64().unwrap_or(0.);
+            // Stored in column-major order, like cgmath.
+            return Matrix4::new(
+                value(0),
+                value(1),
+                value(2),
+                value(3),
+                value(4),
+                value(5),
+                value(6),
+                value(7),
+                value(8),
+                value(9),
+                value(10),
+                value(11),
+                value(12),
+                value(13),
+                value(14),
+                value(15),
+            );
+        }
+    }
+    let translation = numbers(&node["translation"], &[0., 0., 0.]);
+    let rotation = numbers(&node["rotation"], &[0., 0., 0., 1.]);
+    let scale = numbers(&node["scale"], &[1., 1., 1.]);
+    Matrix4::from_translation(Vector3::new(translation[0], translation[1], translation[2]))
+        * Matrix4::from(Quaternion::new(
+            rotation[3],
+            rotation[0],
+            rotation[1],
+            rotation[2],
+        ))
+        * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+}
+
+fn decode_component(component_type: u64, bytes: &[u8], normalized: bool) -> f64 {
+    let (value, range) = match component_type {
+        5120 => (f64::from(bytes[0] as i8), 127.),
+        5121 => (f64::from(bytes[0]), 255.),
+        5122 => (
+            f64::from(i16::from_le_bytes(bytes.try_into().unwrap())),
+            32767.,
+        ),
+        5123 => (
+            f64::from(u16::from_le_bytes(bytes.try_into().unwrap())),
+            65535.,
+        ),
+        5125 => (f64::from(u32::from_le_bytes(bytes.try_into().unwrap())), 1.),
+        _ => (f64::from(f32::from_le_bytes(bytes.try_into().unwrap())), 1.),
+    };
+    if normalized {
+        (value / range).max(-1.)
+    } else {
+        value
+    }
+}
+
+/// Decodes standard or URL safe base64, with or without padding.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in text.bytes().filter(|byte| *byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Decodes `%xx` escapes in a relative URI.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[index], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle in a base64 buffer, drawn through `nodes`.
+    fn triangle(nodes: &str, mode: u64) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": {},
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "mode": {}}}]}}],
+                "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}}],
+                "bufferViews": [{{"buffer": 0, "byteLength": 36}}],
+                "buffers": [{{
+                    "byteLength": 36,
+                    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+                }}]
+            }}"#,
+            nodes, mode
+        )
+    }
+
+    fn parse(contents: &str) -> Result<Mesh> {
+        super::parse(Path::new("test.gltf"), contents.as_bytes(), Path::new(""))
+    }
+
+    #[test]
+    fn parse_embedded() {
+        let mesh = parse(&triangle(
+            r#"[{"translation": [1, 2, 3], "children": [1]}, {"mesh": 0, "scale": [2, 2, 2]}]"#,
+            TRIANGLES,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            mesh.positions,
+            vec![
+                Vector3::new(1., 2., 3.),
+                Vector3::new(3., 2., 3.),
+                Vector3::new(1., 4., 3.),
+            ]
+        );
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+        assert!(mesh.normals.is_empty() && mesh.textures.is_empty());
+        assert_eq!(mesh.material(0).name, "");
+    }
+
+    #[test]
+    fn mirrored_nodes() {
+        let mesh = parse(&triangle(
+            r#"[{"mesh": 0, "scale": [-1, 1, 1]}]"#,
+            TRIANGLES,
+        ))
+        .unwrap();
+        assert_eq!(mesh.triangles, vec![[0, 2, 1]]);
+
+        let mesh = parse(&triangle(r#"[{"mesh": 0}]"#, LINE_LOOP)).unwrap();
+        assert_eq!(mesh.lines, vec![[0, 1], [1, 2], [2, 0]]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        match parse(r#"{"asset": {"version": "1.0"}}"#) {
+            Err(Error::Format { .. }) => {}
+            _ => panic!("expected a format error"),
+        }
+        match parse(&triangle(r#"[{"mesh": 1}]"#, TRIANGLES)) {
+            Err(Error::InvalidIndex { kind, index, .. }) => {
+                assert_eq!((kind, index), ("meshes", 1))
+            }
+            _ => panic!("expected an invalid index"),
+        }
+        match parse(&triangle(r#"[{"mesh": 0, "children": [0]}]"#, TRIANGLES)) {
+            Err(Error::Format { message, .. }) => assert!(message.contains("cycle")),
+            _ => panic!("expected a format error"),
+        }
+
+        // Counts and offsets out of bounds, which must not be allocated for
+        // or overflow.
+        let nodes = r#"[{"mesh": 0}]"#;
+        let huge = "\"count\": 1000000000000000000";
+        for contents in &[
+            triangle(nodes, TRIANGLES).replace("\"count\": 3", huge),
+            triangle(nodes, TRIANGLES)
+                .replace("\"bufferView\": 0, ", "")
+                .replace("\"count\": 3", huge),
+            triangle(nodes, TRIANGLES).replace(
+                "\"byteLength\": 36}",
+                "\"byteLength\": 36, \"byteOffset\": 18446744073709551615}",
+            ),
+            triangle(nodes, TRIANGLES)
+                .replace(
+                    "\"byteLength\": 36}",
+                    "\"byteLength\": 36, \"byteStride\": 0}",
+                )
+                .replace("\"count\": 3", huge),
+        ] {
+            match parse(contents) {
+                Err(Error::Format { .. }) => {}
+                _ => panic!("expected a format error"),
+            }
+        }
+    }
+
+    #[test]
+    fn texture_samplers() {
+        let directory = Path::new("tests/fixtures/gltf");
+        let contents = fs::read_to_string(directory.join("quad.gltf")).unwrap();
+        let mesh = super::parse(Path::new("quad.gltf"), contents.as_bytes(), directory).unwrap();
+        assert_eq!(mesh.materials[0].wrap, [Wrap::Repeat, Wrap::Repeat]);
+
+        let mut json: Value = serde_json::from_str(&contents).unwrap();
+        json["textures"][0]["sampler"] = Value::from(0);
+        json["samplers"] = serde_json::json!([{"wrapS": 33071, "wrapT": 33648}]);
+        let contents = json.to_string();
+        let mesh = super::parse(Path::new("quad.gltf"), contents.as_bytes(), directory).unwrap();
+        assert_eq!(mesh.materials[0].wrap, [Wrap::Clamp, Wrap::MirroredRepeat]);
+    }
+
+    #[test]
+    fn decode_uris() {
+        assert_eq!(decode_base64("aGk="), Some(b"hi".to_vec()));
+        assert_eq!(decode_base64("AP__"), Some(vec![0, 255, 255]));
+        assert_eq!(decode_base64("a!"), None);
+        assert_eq!(percent_decode("my%20model.bin"), "my model.bin");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
@@ -0,0 +1,688 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector2, Vector3, Vector4};
+use image::DynamicImage;
+use log::debug;
+use rayon::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::geometry::clip;
+use crate::geometry::common::{minmax, orthogonal_tangent, tangents as tangents_of};
+use crate::geometry::Triangle;
+use crate::render::common::{shade, NormalMap, SpecularMap};
+use crate::render::material;
+use crate::render::shader::{DepthShader, FlatShader, Shader, Uniforms, Varying, Vertex};
+use crate::render::tile::{Tiled, TILE_SIZE};
+use crate::render::{Camera, DepthBuffer, Material, Renderer, Scene, ShadowMap, ShadowSettings};
+
+/// Pulls lines and points towards the camera so that they win the depth test
+/// against the faces they lie on.
+const LINE_DEPTH_BIAS: f64 = 1e-4;
+/// Number of triangles `render_parallel` runs the vertex stage on at once,
+/// which bounds the memory held by their outputs.
+const BATCH: usize = 1 << 16;
+
+/// An indexed triangle mesh, independent of the file format it was loaded
+/// from. Every vertex attribute array is either empty or holds one value per
+/// position, and triangles, lines and points index into them from 0.
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vector3<f64>>,
+    /// Vertex normals, or empty to shade every triangle flat.
+    pub normals: Vec<Vector3<f64>>,
+    /// Texture coordinates, or empty.
+    pub textures: Vec<Vector3<f64>>,
+    /// Vertex colors multiplied with the diffuse color, or empty for white.
+    pub colors: Vec<Vector3<f64>>,
+    /// Tangents along increasing `u` texture coordinates with the
+    /// handedness of the bitangent in `w`, or empty.
+    pub tangents: Vec<Vector4<f64>>,
+    /// Vertex indices of each triangle, wound counter-clockwise when seen
+    /// from the front.
+    pub triangles: Vec<[u32; 3]>,
+    pub materials: Vec<Material>,
+    /// Index into `materials` of each triangle. Triangles without one use
+    /// the first material, or the default one if there are none.
+    pub triangle_materials: Vec<usize>,
+    /// Vertex indices of line segments, drawn unlit in their vertex colors.
+    pub lines: Vec<[u32; 2]>,
+    /// Vertex indices of points, drawn like lines.
+    pub points: Vec<u32>,
+}
+
+impl Mesh {
+    /// A mesh with only positions and triangles, drawn with the default
+    /// material.
+    pub fn new(positions: Vec<Vector3<f64>>, triangles: Vec<[u32; 3]>) -> Mesh {
+        Mesh {
+            positions,
+            triangles,
+            ..Mesh::default()
+        }
+    }
+
+    /// Uses `texture` as the diffuse map of every material.
+    pub fn with_texture(mut self, texture: DynamicImage) -> Mesh {
+        for material in self.materials_mut() {
+            material.diffuse_map = Some(texture.clone());
+        }
+        self
+    }
+
+    /// Uses `normal_map` for every material.
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Mesh {
+        for material in self.materials_mut() {
+            material.normal_map = Some(normal_map.clone());
+        }
+        self
+    }
+
+    /// Uses `specular_map` for every material.
+    pub fn with_specular_map(mut self, specular_map: SpecularMap) -> Mesh {
+        for material in self.materials_mut() {
+            material.specular_map = Some(specular_map.clone());
+        }
+        self
+    }
+
+    /// The materials of the mesh, adding a default one to change if there
+    /// are none.
+    pub fn materials_mut(&mut self) -> &mut [Material] {
+        if self.materials.is_empty() {
+            self.materials.push(Material::default());
+        }
+        &mut self.materials
+    }
+
+    /// Material of the triangle at `index`.
+    pub fn material(&self, index: usize) -> &Material {
+        self.triangle_materials
+            .get(index)
+            .and_then(|material| self.materials.get(*material))
+            .or_else(|| self.materials.first())
+            .unwrap_or(&material::DEFAULT)
+    }
+
+    /// Computes `tangents` from the texture coordinates, for normal mapped
+    /// meshes loaded without them. Does nothing without texture coordinates.
+    pub fn generate_tangents(&mut self) {
+        if self.textures.len() != self.positions.len() {
+            return;
+        }
+        let mut tangents = vec![Vector3::new(0., 0., 0.); self.positions.len()];
+        let mut bitangents = tangents.clone();
+        let mut face_normals = tangents.clone();
+        let count = self.positions.len();
+        for triangle in &self.triangles {
+            if triangle.iter().any(|vertex| *vertex as usize >= count) {
+                continue;
+            }
+            let corner = |index: usize| triangle[index] as usize;
+            let positions = [
+                self.positions[corner(0)],
+                self.positions[corner(1)],
+                self.positions[corner(2)],
+            ];
+            let normal = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+            let tangent = tangents_of(
+                positions,
+                [
+                    self.textures[corner(0)],
+                    self.textures[corner(1)],
+                    self.textures[corner(2)],
+                ],
+            );
+            for index in 0..3 {
+                face_normals[corner(index)] += normal;
+                if let Some((tangent, bitangent)) = tangent {
+                    tangents[corner(index)] += tangent;
+                    bitangents[corner(index)] += bitangent;
+                }
+            }
+        }
+        self.tangents = (0..self.positions.len())
+            .map(|index| {
+                let normal = match self.normals.get(index) {
+                    Some(normal) => *normal,
+                    None => face_normals[index],
+                };
+                orthogonal_tangent(tangents[index], bitangents[index], normal)
+            })
+            .collect();
+    }
+
+    /// Draws the mesh into `scene` with flat shading.
+    pub fn render(&self, renderer: &mut impl Renderer, scene: &Scene) -> Result<bool> {
+        self.render_with(renderer, scene, &FlatShader)
+    }
+
+    /// Draws the mesh into `scene`, running `shader` for every vertex and
+    /// covered pixel.
+    pub fn render_with<S: Shader>(
+        &self,
+        renderer: &mut impl Renderer,
+        scene: &Scene,
+        shader: &S,
+    ) -> Result<bool> {
+        let shadows = match scene.shadows {
+            Some(settings) => self.shadow_maps(scene, settings, false)?,
+            None => Vec::new(),
+        };
+
+        let (width, height) = renderer.get_size();
+        let uniforms = Uniforms {
+            shadows: &shadows,
+            ..Uniforms::new(scene, width, height)
+        };
+        self.draw(renderer, &uniforms, shader)?;
+        self.draw_lines(renderer, &uniforms)?;
+        Ok(true)
+    }
+
+    /// Draws the mesh like `render_with`, but splits the image into tiles
+    /// that are rasterized in parallel on the rayon thread pool. Every tile
+    /// draws the triangles covering it in order, so the image is the same
+    /// as the one `render_with` draws.
+    pub fn render_parallel<R, S>(&self, renderer: &mut R, scene: &Scene, shader: &S) -> Result<bool>
+    where
+        R: Tiled,
+        S: Shader + Sync,
+        S::Varying: Send + Sync,
+    {
+        let shadows = match scene.shadows {
+            Some(settings) => self.shadow_maps(scene, settings, true)?,
+            None => Vec::new(),
+        };
+
+        let (width, height) = renderer.get_size();
+        let uniforms = Uniforms {
+            shadows: &shadows,
+            ..Uniforms::new(scene, width, height)
+        };
+        self.draw_tiled(renderer, &uniforms, shader)?;
+        self.draw_lines(renderer, &uniforms)?;
+        Ok(true)
+    }
+
+    pub(crate) fn position(&self, index: u32) -> Result<Vector3<f64>> {
+        self.positions
+            .get(index as usize)
+            .cloned()
+            .ok_or(Error::InvalidIndex {
+                kind: "vertex",
+                index: i64::from(index),
+                len: self.positions.len(),
+            })
+    }
+
+    /// Vertex color at `index`, white if the mesh has none.
+    fn color(&self, index: u32) -> Vector3<f64> {
+        match self.colors.get(index as usize) {
+            Some(color) => *color,
+            None => Vertex::default().color,
+        }
+    }
+
+    /// World space bounding sphere of the mesh.
+    fn bounds(&self, model: &Matrix4<f64>) -> (Point3<f64>, f64) {
+        if self.positions.is_empty() {
+            return (Point3::new(0., 0., 0.), 0.);
+        }
+        let world: Vec<Vector3<f64>> = self
+            .positions
+            .iter()
+            .map(|position| (model * position.extend(1.)).truncate())
+            .collect();
+        let (min, max) = minmax(&world);
+        let center = (min + max) / 2.;
+        (Point3::from_vec(center), (max - center).magnitude())
+    }
+
+    /// Renders the depth seen from every light that casts shadows, in
+    /// parallel tiles if `parallel` is set.
+    fn shadow_maps(
+        &self,
+        scene: &Scene,
+        settings: ShadowSettings,
+        parallel: bool,
+    ) -> Result<Vec<Option<ShadowMap>>> {
+        if settings.resolution == 0 {
+            return Err(Error::Argument(String::from(
+                "shadow map resolution must be at least 1",
+            )));
+        }
+        let (center, radius) = self.bounds(&scene.camera.model);
+        let mut shadows: Vec<Option<ShadowMap>> = Vec::new();
+        for light in &scene.lights {
+            let camera = match ShadowMap::camera(&light.kind, center, radius) {
+                Some(camera) => Camera {
+                    model: scene.camera.model,
+                    ..camera
+                },
+                None => {
+                    shadows.push(None);
+                    continue;
+                }
+            };
+            debug!("Rendering shadow map from {:?}", camera.eye);
+            let light_scene = Scene::unlit(camera);
+            let mut depth = DepthBuffer::new(settings.resolution, settings.resolution);
+            let uniforms = Uniforms::new(&light_scene, settings.resolution, settings.resolution);
+            if parallel {
+                self.draw_tiled(&mut depth, &uniforms, &DepthShader)?;
+            } else {
+                self.draw(&mut depth, &uniforms, &DepthShader)?;
+            }
+            shadows.push(Some(ShadowMap::new(depth, &camera, settings)));
+        }
+        Ok(shadows)
+    }
+
+    fn draw<S: Shader>(
+        &self,
+        renderer: &mut impl Renderer,
+        uniforms: &Uniforms,
+        shader: &S,
+    ) -> Result<bool> {
+        let (width, height) = renderer.get_size();
+        let viewport = Camera::viewport(width, height);
+
+        let guard = clip::guard_band(width, height);
+
+        for index in 0..self.triangles.len() {
+            let uniforms = &Uniforms {
+                material: self.material(index),
+                ..*uniforms
+            };
+            for triangle in self.setup(index, uniforms, shader, &viewport, guard)? {
+                triangle.render(renderer, shader, uniforms)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Draws the triangles like `draw`, in batches: the vertex stage of a
+    /// batch runs in parallel, its triangles are binned into the tiles they
+    /// overlap, and then the tiles are rasterized in parallel.
+    fn draw_tiled<R, S>(&self, renderer: &mut R, uniforms: &Uniforms, shader: &S) -> Result<bool>
+    where
+        R: Tiled,
+        S: Shader + Sync,
+        S::Varying: Send + Sync,
+    {
+        let (width, height) = renderer.get_size();
+        let viewport = Camera::viewport(width, height);
+        let guard = clip::guard_band(width, height);
+        let columns = ((width + TILE_SIZE - 1) / TILE_SIZE) as usize;
+        let mut tiles = renderer.tiles(TILE_SIZE);
+        let mut bins: Vec<Vec<usize>> = vec![Vec::new(); tiles.len()];
+
+        for start in (0..self.triangles.len()).step_by(BATCH) {
+            let end = (start + BATCH).min(self.triangles.len());
+            let triangles: Vec<(usize, Triangle<S::Varying>)> = (start..end)
+                .into_par_iter()
+                .map(|index| {
+                    let triangles = self.setup(index, uniforms, shader, &viewport, guard)?;
+                    Ok(triangles.into_iter().map(move |triangle| (index, triangle)))
+                })
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+
+            bins.iter_mut().for_each(Vec::clear);
+            for (offset, (_, triangle)) in triangles.iter().enumerate() {
+                let (min, max) = match triangle.bounds() {
+                    Some(bounds) => bounds,
+                    None => continue,
+                };
+                if min[0] >= i64::from(width)
+                    || min[1] >= i64::from(height)
+                    || max[0] < 0
+                    || max[1] < 0
+                {
+                    continue;
+                }
+                let min = [min[0].max(0) as u32, min[1].max(0) as u32];
+                let max = [
+                    max[0].min(i64::from(width) - 1) as u32,
+                    max[1].min(i64::from(height) - 1) as u32,
+                ];
+                for row in min[1] / TILE_SIZE..=max[1] / TILE_SIZE {
+                    for column in min[0] / TILE_SIZE..=max[0] / TILE_SIZE {
+                        bins[row as usize * columns + column as usize].push(offset);
+                    }
+                }
+            }
+
+            tiles.par_iter_mut().zip(bins.par_iter()).try_for_each(
+                |(tile, bin)| -> Result<()> {
+                    for &offset in bin {
+                        let (index, triangle) = &triangles[offset];
+                        let uniforms = &Uniforms {
+                            material: self.material(*index),
+                            ..*uniforms
+                        };
+                        triangle.render_tile(tile, shader, uniforms)?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+        Ok(true)
+    }
+
+    /// Runs the vertex stage on the corners of triangle `index` and clips
+    /// it against the view volume with its sides scaled by `guard`,
+    /// returning the screen space triangles left. There are none if it is
+    /// outside the view or facing away from the camera.
+    fn setup<S: Shader>(
+        &self,
+        index: usize,
+        uniforms: &Uniforms,
+        shader: &S,
+        viewport: &Matrix4<f64>,
+        guard: Vector2<f64>,
+    ) -> Result<Vec<Triangle<S::Varying>>> {
+        let uniforms = &Uniforms {
+            material: self.material(index),
+            ..*uniforms
+        };
+        let triangle = self.triangles[index];
+        let positions = [
+            self.position(triangle[0])?,
+            self.position(triangle[1])?,
+            self.position(triangle[2])?,
+        ];
+        let face_normal = (positions[1] - positions[0])
+            .cross(positions[2] - positions[0])
+            .normalize();
+
+        let mut clip = [Vector4::new(0., 0., 0., 0.); 3];
+        let mut varyings: Vec<S::Varying> = Vec::with_capacity(3);
+        for (corner, &vertex) in triangle.iter().enumerate() {
+            let attribute = |values: &[Vector3<f64>], default: Vector3<f64>| {
+                values.get(vertex as usize).cloned().unwrap_or(default)
+            };
+            let vertex = Vertex {
+                position: positions[corner],
+                normal: attribute(&self.normals, face_normal),
+                tangent: self
+                    .tangents
+                    .get(vertex as usize)
+                    .cloned()
+                    .unwrap_or_else(|| Vertex::default().tangent),
+                texture: attribute(&self.textures, Vertex::default().texture),
+                color: self.color(vertex),
+                face_normal,
+            };
+            let (position, varying) = shader.vertex(uniforms, &vertex);
+            clip[corner] = position;
+            varyings.push(varying);
+        }
+
+        let polygon = clip::clip_triangle(clip, guard);
+        if polygon.len() < 3
+            || polygon
+                .iter()
+                .any(|vertex| vertex.position.w <= f64::EPSILON)
+        {
+            return Ok(Vec::new());
+        }
+        let screen: Vec<Vector3<f64>> = polygon
+            .iter()
+            .map(|vertex| (viewport * vertex.position).truncate() / vertex.position.w)
+            .collect();
+
+        // Polygons wound counter-clockwise on screen are facing the camera.
+        let area: f64 = (1..screen.len() - 1)
+            .map(|corner| {
+                (screen[corner] - screen[0])
+                    .cross(screen[corner + 1] - screen[0])
+                    .z
+            })
+            .sum();
+        if area <= 0. {
+            return Ok(Vec::new());
+        }
+
+        let corner = |corner: usize| {
+            let weights = polygon[corner].weights;
+            (
+                screen[corner],
+                polygon[corner].position.w,
+                S::Varying::weighted(&varyings[0], &varyings[1], &varyings[2], weights),
+            )
+        };
+        let mut triangles = Vec::with_capacity(polygon.len() - 2);
+        let first = corner(0);
+        for index in 1..polygon.len() - 1 {
+            let (b, c) = (corner(index), corner(index + 1));
+            triangles.push(
+                Triangle::new(first.0, b.0, c.0, [first.2, b.2, c.2])?.with_w([first.1, b.1, c.1]),
+            );
+        }
+        Ok(triangles)
+    }
+
+    /// Draws `lines` and `points` unlit, in their vertex colors.
+    fn draw_lines(&self, renderer: &mut impl Renderer, uniforms: &Uniforms) -> Result<()> {
+        let (width, height) = renderer.get_size();
+        let viewport = Camera::viewport(width, height);
+        let view = Vector2::new(1., 1.);
+        let screen = |clip: Vector4<f64>| {
+            (viewport * clip).truncate() / clip.w + Vector3::new(0., 0., LINE_DEPTH_BIAS)
+        };
+        let mut plot = |position: Vector3<f64>, color: Vector3<f64>| {
+            let pixel = Vector3::new(position.x.round(), position.y.round(), position.z);
+            renderer.set_pixel(pixel, shade([255, 255, 255], color));
+        };
+
+        for line in &self.lines {
+            let (start, end) = (
+                uniforms.clip(self.position(line[0])?),
+                uniforms.clip(self.position(line[1])?),
+            );
+            let (t0, t1) = match clip::clip_line(start, end, view) {
+                Some(range) => range,
+                None => continue,
+            };
+            let (start_color, end_color) = (self.color(line[0]), self.color(line[1]));
+            let (start, end) = (
+                (
+                    screen(start + (end - start) * t0),
+                    start_color + (end_color - start_color) * t0,
+                ),
+                (
+                    screen(start + (end - start) * t1),
+                    start_color + (end_color - start_color) * t1,
+                ),
+            );
+            let delta = end.0 - start.0;
+            let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.);
+            for step in 0..=steps as u32 {
+                let t = f64::from(step) / steps;
+                plot(start.0 + delta * t, start.1 + (end.1 - start.1) * t);
+            }
+        }
+        for &point in &self.points {
+            let clip = uniforms.clip(self.position(point)?);
+            if clip::inside(clip, view) {
+                plot(screen(clip), self.color(point));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::png::PNG;
+    use crate::render::shader::{GouraudShader, SolidShader};
+
+    #[test]
+    fn render_mesh() {
+        let mesh = Mesh::new(
+            vec![
+                Vector3::new(-1., -1., 0.),
+                Vector3::new(1., -1., 0.),
+                Vector3::new(1., 1., 0.),
+                Vector3::new(-1., 1., 0.),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        );
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        mesh.render(&mut renderer, &Scene::default()).unwrap();
+        assert_eq!(renderer.get_pixel(5, 15), Some([255, 255, 255]));
+        assert_eq!(renderer.get_pixel(15, 5), Some([255, 255, 255]));
+    }
+
+    #[test]
+    fn near_plane_clipping() {
+        // A floor running from behind the eye to beyond the horizon.
+        let mesh = Mesh::new(
+            vec![
+                Vector3::new(-10., -1., 10.),
+                Vector3::new(10., -1., 10.),
+                Vector3::new(10., -1., -10.),
+                Vector3::new(-10., -1., -10.),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        );
+        let camera = Camera::new(
+            Point3::new(0., 0., 0.),
+            Point3::new(0., 0., -1.),
+            Vector3::unit_y(),
+        );
+        let scene = Scene::new(camera);
+        let shader = SolidShader {
+            color: [255, 255, 255],
+        };
+
+        let mut serial: PNG = Renderer::new(20, 20);
+        mesh.render_with(&mut serial, &scene, &shader).unwrap();
+        let mut parallel: PNG = Renderer::new(20, 20);
+        mesh.render_parallel(&mut parallel, &scene, &shader)
+            .unwrap();
+        for y in 0..20 {
+            for x in 0..20 {
+                // The far edge of the floor is at 7.6 pixels up.
+                let expected = if y <= 7 { [255, 255, 255] } else { [0, 0, 0] };
+                assert_eq!(serial.get_pixel(x, y), Some(expected), "({}, {})", x, y);
+                assert_eq!(parallel.get_pixel(x, y), Some(expected), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn render_parallel() {
+        // Overlapping triangles, some reaching past the edges of the image.
+        let mut seed: u32 = 7;
+        let mut random = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            f64::from(seed >> 8) / f64::from(1 << 24) * 2.6 - 1.3
+        };
+        // Every other triangle lies in the same plane, so that the order in
+        // which they are drawn decides the depth test.
+        let positions: Vec<Vector3<f64>> = (0..150)
+            .map(|index| {
+                let z = random();
+                Vector3::new(random(), random(), if index % 6 < 3 { 0. } else { z })
+            })
+            .collect();
+        let triangles: Vec<[u32; 3]> = (0..50)
+            .map(|index| [index * 3, index * 3 + 1, index * 3 + 2])
+            .collect();
+        let mut mesh = Mesh::new(positions, triangles);
+        mesh.materials = vec![
+            Material::default().with_diffuse(Vector3::new(1., 0.5, 0.)),
+            Material::default().with_diffuse(Vector3::new(0., 0.5, 1.)),
+        ];
+        mesh.triangle_materials = (0..50).map(|index| index % 2).collect();
+        let scene = Scene::default().with_shadows(ShadowSettings::default());
+
+        let mut serial: PNG = Renderer::new(100, 70);
+        mesh.render_with(&mut serial, &scene, &GouraudShader)
+            .unwrap();
+        let mut parallel: PNG = Renderer::new(100, 70);
+        mesh.render_parallel(&mut parallel, &scene, &GouraudShader)
+            .unwrap();
+        assert!(serial.pixels().iter().any(|channel| *channel != 0));
+        assert_eq!(parallel.pixels(), serial.pixels());
+        for (x, y) in (0..100).flat_map(|x| (0..70).map(move |y| (f64::from(x), f64::from(y)))) {
+            assert_eq!(parallel.depth().depth(x, y), serial.depth().depth(x, y));
+        }
+
+        let empty = Scene::default().with_shadows(ShadowSettings {
+            resolution: 0,
+            ..ShadowSettings::default()
+        });
+        assert!(mesh
+            .render_with(&mut serial, &empty, &GouraudShader)
+            .is_err());
+        assert!(mesh
+            .render_parallel(&mut parallel, &empty, &GouraudShader)
+            .is_err());
+    }
+
+    #[test]
+    fn triangle_materials() {
+        let mut mesh = Mesh::new(
+            vec![
+                Vector3::new(-1., -1., 0.),
+                Vector3::new(1., -1., 0.),
+                Vector3::new(1., 1., 0.),
+                Vector3::new(-1., 1., 0.),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        );
+        assert_eq!(mesh.material(1).name, "");
+        mesh.materials = vec![
+            Material::new(String::from("red")).with_diffuse(Vector3::new(1., 0., 0.)),
+            Material::new(String::from("blue")).with_diffuse(Vector3::new(0., 0., 1.)),
+        ];
+        mesh.triangle_materials = vec![0, 1];
+        for material in mesh.materials_mut() {
+            material.illumination = 0;
+        }
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        mesh.render(&mut renderer, &Scene::default()).unwrap();
+        assert_eq!(renderer.get_pixel(15, 5), Some([255, 0, 0]));
+        assert_eq!(renderer.get_pixel(5, 15), Some([0, 0, 255]));
+    }
+
+    #[test]
+    fn generate_tangents() {
+        let mut mesh = Mesh::new(
+            vec![
+                Vector3::new(0., 0., 0.),
+                Vector3::new(1., 0., 0.),
+                Vector3::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2]],
+        );
+        mesh.generate_tangents();
+        assert!(mesh.tangents.is_empty());
+
+        // Texture coordinates mirrored along `v`.
+        mesh.textures = vec![
+            Vector3::new(0., 1., 0.),
+            Vector3::new(1., 1., 0.),
+            Vector3::new(0., 0., 0.),
+        ];
+        mesh.generate_tangents();
+        assert_eq!(mesh.tangents, vec![Vector4::new(1., 0., 0., -1.); 3]);
+    }
+
+    #[test]
+    fn invalid_index() {
+        let mesh = Mesh::new(vec![Vector3::new(0., 0., 0.)], vec![[0, 1, 2]]);
+
+        let mut renderer: PNG = Renderer::new(4, 4);
+        match mesh.render(&mut renderer, &Scene::default()) {
+            Err(Error::InvalidIndex { index, len, .. }) => assert_eq!((index, len), (1, 1)),
+            _ => panic!("expected an invalid index"),
+        }
+    }
+}
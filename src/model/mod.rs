@@ -1 +1,79 @@
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+pub mod cache;
+pub mod gltf;
+pub mod mesh;
+pub mod mtl;
 pub mod obj;
+mod parse;
+pub mod ply;
+pub mod stl;
+
+use mesh::Mesh;
+use obj::Object;
+
+/// File formats that models can be loaded from. All but glTF can also be
+/// written, see `save`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Gltf,
+    Obj,
+    Ply,
+    Stl,
+}
+
+impl Format {
+    /// Picks the format from the extension of `path`. Files with any other
+    /// extension are read as OBJ.
+    pub fn from_path(path: &Path) -> Format {
+        Format::from_extension(path).unwrap_or(Format::Obj)
+    }
+
+    /// Format of the extension of `path`, if it is a known one.
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "gltf" | "glb" => Some(Format::Gltf),
+            "obj" => Some(Format::Obj),
+            "ply" => Some(Format::Ply),
+            "stl" => Some(Format::Stl),
+            _ => None,
+        }
+    }
+}
+
+/// Loads a model in any supported format, see `Format::from_path`. OBJ
+/// files are loaded through their binary cache, see `Object::load_cached`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    match Format::from_path(path) {
+        Format::Gltf => gltf::load(path),
+        Format::Obj => Ok(Object::load_cached(path)?.to_mesh()),
+        Format::Ply => ply::load(path),
+        Format::Stl => stl::load(path),
+    }
+}
+
+/// Writes a model in the format given by the extension of `path`: OBJ with
+/// a material library, binary little-endian PLY or binary STL.
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<()> {
+    let path = path.as_ref();
+    match Format::from_extension(path) {
+        Some(Format::Obj) => obj::save(mesh, path),
+        Some(Format::Ply) => ply::save(mesh, path),
+        Some(Format::Stl) => stl::save(mesh, path),
+        Some(Format::Gltf) => Err(Error::Argument(String::from(
+            "writing glTF files is not supported",
+        ))),
+        None => Err(Error::Argument(format!(
+            "cannot tell the format to write {} in, use an .obj, .ply or .stl extension",
+            path.display()
+        ))),
+    }
+}
@@ -0,0 +1,309 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use cgmath::Vector3;
+use image::{open, ColorType, DynamicImage};
+use log::{debug, warn};
+
+use crate::error::Result;
+use crate::model::parse::{rest, tokenize, Location, Token};
+use crate::render::common::{NormalMap, NormalSpace, SpecularMap};
+use crate::render::Material;
+
+/// Loads the materials of a Wavefront `.mtl` library, opening their textures
+/// relative to `directory`.
+pub fn load<P: AsRef<Path>>(path: P, directory: &Path) -> Result<Vec<Material>> {
+    let path = path.as_ref();
+    debug!("Loading material library: {}", path.display());
+    let file_contents = fs::read_to_string(path)?;
+    parse(path, &file_contents, directory)
+}
+
+/// Writes `materials` to a `.mtl` library. Their textures are saved as PNG
+/// files next to it, named after the library, e.g. `model_0_diffuse.png`.
+pub fn save<P: AsRef<Path>>(materials: &[Material], path: P) -> Result<()> {
+    let path = path.as_ref();
+    debug!("Saving material library: {}", path.display());
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(file, "# Written by cpuengine")?;
+    for (index, material) in materials.iter().enumerate() {
+        writeln!(file, "\nnewmtl {}", name(material, index))?;
+        let Material {
+            ambient,
+            diffuse,
+            specular,
+            ..
+        } = material;
+        writeln!(file, "Ka {} {} {}", ambient.x, ambient.y, ambient.z)?;
+        writeln!(file, "Kd {} {} {}", diffuse.x, diffuse.y, diffuse.z)?;
+        writeln!(file, "Ks {} {} {}", specular.x, specular.y, specular.z)?;
+        writeln!(file, "Ns {}", material.shininess)?;
+        writeln!(file, "d {}", material.opacity)?;
+        writeln!(file, "illum {}", material.illumination)?;
+
+        let normal_map = match &material.normal_map {
+            Some(map) if map.space == NormalSpace::Tangent => Some(&map.image),
+            Some(_) => {
+                warn!(
+                    "Skipping the object space normal map of material `{}`: MTL only has tangent space maps",
+                    material.name
+                );
+                None
+            }
+            None => None,
+        };
+        let maps = [
+            ("map_Kd", "diffuse", material.diffuse_map.as_ref()),
+            (
+                "map_Ks",
+                "specular",
+                material.specular_map.as_ref().map(|map| &map.image),
+            ),
+            ("norm", "normal", normal_map),
+            ("map_d", "alpha", material.alpha_map.as_ref()),
+        ];
+        for (statement, kind, image) in maps.iter() {
+            if let Some(image) = image {
+                let file_name = format!("{}_{}_{}.png", stem, index, kind);
+                // Textures are kept flipped, see `texture`.
+                image.flipv().save(directory.join(&file_name))?;
+                writeln!(file, "{} {}", statement, file_name)?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Name `material` is written under: its own, or one made up from its index
+/// when it has none, since `usemtl` cannot refer to an empty name.
+pub(crate) fn name(material: &Material, index: usize) -> String {
+    if material.name.trim().is_empty() {
+        format!("material_{}", index)
+    } else {
+        material.name.clone()
+    }
+}
+
+pub(crate) fn parse(path: &Path, file_contents: &str, directory: &Path) -> Result<Vec<Material>> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (index, line) in file_contents.lines().enumerate() {
+        let mut tokens = tokenize(line);
+
+        if tokens.is_empty() || tokens[0].text.starts_with('#') {
+            continue;
+        }
+
+        let location = Location {
+            path,
+            line: index + 1,
+        };
+        let statement = tokens.remove(0);
+
+        if statement.text == "newmtl" {
+            let name = tokens
+                .first()
+                .map(|token| rest(line, token))
+                .ok_or_else(|| location.error(statement.column, "missing material name"))?;
+            materials.push(Material::new(String::from(name)));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => {
+                return Err(location.error(
+                    statement.column,
+                    format!("`{}` before any `newmtl`", statement.text),
+                ))
+            }
+        };
+        match statement.text {
+            "Ka" => material.ambient = color(&location, &statement, &tokens)?,
+            "Kd" => material.diffuse = color(&location, &statement, &tokens)?,
+            "Ks" => material.specular = color(&location, &statement, &tokens)?,
+            "Ns" => {
+                material.shininess = location.parse(argument(&location, &statement, &tokens)?)?
+            }
+            "d" => material.opacity = location.parse(argument(&location, &statement, &tokens)?)?,
+            "Tr" => {
+                let transparency: f64 =
+                    location.parse(argument(&location, &statement, &tokens)?)?;
+                material.opacity = 1. - transparency;
+            }
+            "illum" => {
+                material.illumination = location.parse(argument(&location, &statement, &tokens)?)?
+            }
+            "map_Kd" => {
+                let file = file(&location, &statement, &tokens)?;
+                material.diffuse_map = texture(directory, file);
+            }
+            "map_Ks" => {
+                let file = file(&location, &statement, &tokens)?;
+                material.specular_map = load_map(directory, file, |path| SpecularMap::open(path));
+            }
+            "norm" => {
+                let file = file(&location, &statement, &tokens)?;
+                material.normal_map = load_map(directory, file, |path| {
+                    NormalMap::open(path, NormalSpace::Tangent)
+                });
+            }
+            // Exporters such as Blender write tangent space normal maps here,
+            // but single channel images are height maps, which are skipped.
+            "map_Bump" | "map_bump" | "bump" if material.normal_map.is_none() => {
+                let file = file(&location, &statement, &tokens)?;
+                material.normal_map = load_map(directory, file, |path| {
+                    NormalMap::open(path, NormalSpace::Tangent)
+                })
+                .filter(|map| match map.image.color() {
+                    ColorType::Gray(_) | ColorType::GrayA(_) => {
+                        warn!(
+                            "Skipping bump map {}: height maps are not supported",
+                            directory.join(file).display()
+                        );
+                        false
+                    }
+                    _ => true,
+                });
+            }
+            "map_d" => {
+                let file = file(&location, &statement, &tokens)?;
+                material.alpha_map = texture(directory, file);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn argument<'a, 'b>(
+    location: &Location,
+    statement: &Token,
+    tokens: &'b [Token<'a>],
+) -> Result<&'b Token<'a>> {
+    tokens.first().ok_or_else(|| {
+        location.error(
+            statement.column,
+            format!("missing value for `{}`", statement.text),
+        )
+    })
+}
+
+/// An `r [g b]` color; a single value is used for all three channels.
+fn color(location: &Location, statement: &Token, tokens: &[Token]) -> Result<Vector3<f64>> {
+    if tokens.len() == 1 {
+        let value: f64 = location.parse(&tokens[0])?;
+        return Ok(Vector3::new(value, value, value));
+    }
+    location.vector(statement.column, tokens, 3)
+}
+
+/// File name of a texture statement. Any options such as `-bm 1` come before
+/// it, so it is taken to be the last word.
+fn file<'a>(location: &Location, statement: &Token, tokens: &[Token<'a>]) -> Result<&'a str> {
+    tokens.last().map(|token| token.text).ok_or_else(|| {
+        location.error(
+            statement.column,
+            format!("missing file for `{}`", statement.text),
+        )
+    })
+}
+
+/// Opens a texture referenced by a material, flipped like the diffuse
+/// texture given on the command line.
+fn texture(directory: &Path, file: &str) -> Option<DynamicImage> {
+    load_map(directory, file, |path| Ok(open(path)?.flipv()))
+}
+
+/// Opens a map referenced by a material. Missing or broken textures are
+/// common in exported models, so they are reported and skipped rather than
+/// failing the whole load.
+fn load_map<T, F: FnOnce(&Path) -> Result<T>>(directory: &Path, file: &str, open: F) -> Option<T> {
+    let path = directory.join(file);
+    match open(&path) {
+        Ok(map) => Some(map),
+        Err(error) => {
+            warn!("Skipping texture {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn parse(contents: &str) -> Result<Vec<Material>> {
+        super::parse(Path::new("test.mtl"), contents, Path::new("missing"))
+    }
+
+    #[test]
+    fn parse_materials() {
+        let materials = parse(
+            "# two materials\n\
+             newmtl skin\nKa 0.1 0.1 0.1\nKd 0.8 0.6 0.5\nKs 0.2\nNs 10\nillum 2\n\
+             newmtl glass pane\nd 0.25\nTr 0.5\nillum 1\nmap_Kd -bm 1 missing.png\n",
+        )
+        .unwrap();
+
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].name, "skin");
+        assert_eq!(materials[0].ambient, Vector3::new(0.1, 0.1, 0.1));
+        assert_eq!(materials[0].diffuse, Vector3::new(0.8, 0.6, 0.5));
+        assert_eq!(materials[0].specular, Vector3::new(0.2, 0.2, 0.2));
+        assert_eq!(materials[0].shininess, 10.);
+        assert_eq!(materials[1].name, "glass pane");
+        assert_eq!(materials[1].opacity, 0.5);
+        assert_eq!(materials[1].illumination, 1);
+        assert!(materials[1].diffuse_map.is_none());
+    }
+
+    #[test]
+    fn bump_maps() {
+        let directory =
+            std::env::temp_dir().join(format!("cpuengine-bump-maps-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        image::GrayImage::new(2, 2)
+            .save(directory.join("height.png"))
+            .unwrap();
+        image::RgbImage::new(2, 2)
+            .save(directory.join("normal.png"))
+            .unwrap();
+
+        // Color `map_Bump` images are normal maps, greyscale ones height maps.
+        let materials = super::parse(
+            Path::new("test.mtl"),
+            "newmtl normal\nmap_Bump -bm 2 normal.png\n\
+             newmtl height\nbump height.png\n\
+             newmtl both\nnorm normal.png\nmap_Bump height.png\n",
+            &directory,
+        )
+        .unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(materials[0].normal_map.is_some());
+        assert!(materials[1].normal_map.is_none());
+        assert!(materials[2].normal_map.is_some());
+    }
+
+    #[test]
+    fn parse_errors() {
+        match parse("Kd 1 1 1\n") {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (1, 1)),
+            _ => panic!("expected a parse error"),
+        }
+        match parse("newmtl a\nNs shiny\n") {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (2, 4)),
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
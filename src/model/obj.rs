@@ -1,160 +1,1314 @@
-use std::error;
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
-use cgmath::{InnerSpace, Vector3};
-use image::DynamicImage;
-use log::debug;
+use cgmath::{Angle, Deg, InnerSpace, Vector3, Vector4, Zero};
+use log::{debug, warn};
 
-use crate::geometry::Triangle;
-use crate::render::Renderer;
+use crate::error::{Error, Result};
+use crate::geometry::common::{orthogonal_tangent, tangents as tangents_of, triangulate};
+use crate::model::cache;
+use crate::model::mesh::Mesh;
+use crate::model::mtl;
+use crate::model::parse::{self, rest, tokenize_into, Location, Token};
+use crate::render::shader::Vertex;
+use crate::render::Material;
+
+const WHITE: Vector3<f64> = Vector3::new(1., 1., 1.);
 
 pub struct Face {
     pub vertex: u32,
     pub texture: u32,
     pub normal: u32,
+    pub tangent: u32,
+}
+
+/// How the normals of the faces around a vertex are weighted when they are
+/// averaged into a vertex normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalWeighting {
+    /// By face area, so that large faces dominate.
+    Area,
+    /// By the angle of each face at the vertex, which does not depend on how
+    /// the surface was split into faces.
+    Angle,
 }
 
+/// Settings for generating the normals of faces without `vn`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalSettings {
+    pub weighting: NormalWeighting,
+    /// Faces meeting at a larger angle than this keep a hard edge between
+    /// them, even within a smoothing group.
+    pub crease_angle: Deg<f64>,
+}
+
+impl Default for NormalSettings {
+    fn default() -> NormalSettings {
+        NormalSettings {
+            weighting: NormalWeighting::Angle,
+            crease_angle: Deg(60.),
+        }
+    }
+}
+
+/// A face around a vertex and its weighted normal, see
+/// `Object::generate_normals`.
+type Corner = (usize, Vector3<f64>);
+
+/// A named part of an object, from an `o` or `g` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Indices into `Object::faces` of the faces in this part.
+    pub faces: Vec<usize>,
+}
+
+/// A Wavefront OBJ file as written, with 1-based indices into separate
+/// position, texture coordinate and normal lists. Convert it with `to_mesh`
+/// to render it.
 pub struct Object {
     pub faces: Vec<Vec<Face>>,
+    /// Triangles covering each face, as indices into its vertices. Faces
+    /// are triangulated once when loading, so concave polygons are drawn
+    /// correctly.
+    pub triangles: Vec<Vec<[usize; 3]>>,
     pub vertices: Vec<Vector3<f64>>,
+    /// Normals from the file, followed by the generated ones.
     pub normals: Vec<Vector3<f64>>,
+    /// Number of `normals` read from the file.
+    pub authored_normals: usize,
     pub textures: Vec<Vector3<f64>>,
-    pub texture: DynamicImage,
+    /// Colors from the `v x y z r g b` extension, one per vertex, or empty
+    /// if the file has none.
+    pub colors: Vec<Vector3<f64>>,
+    /// Tangent along increasing `u` texture coordinates with the handedness
+    /// of the bitangent in `w`, generated when loading.
+    pub tangents: Vec<Vector4<f64>>,
+    /// Files named by `mtllib` statements, relative to the OBJ file.
+    pub material_libraries: Vec<String>,
+    /// Materials from the `mtllib` libraries, plus a default one for faces
+    /// without a known `usemtl`.
+    pub materials: Vec<Material>,
+    /// Index into `materials` of each face.
+    pub face_materials: Vec<usize>,
+    /// Smoothing group of each face, 0 when smoothing is off. Files without
+    /// any `s` statement are smoothed as a single group, leaving hard edges
+    /// to the crease angle.
+    pub smoothing_groups: Vec<u32>,
+    /// Parts named by `o` statements.
+    pub objects: Vec<Group>,
+    /// Parts named by `g` statements. A face may belong to several groups.
+    pub groups: Vec<Group>,
+    /// Whether each face is drawn, see `hide` and `show_only`.
+    pub visible: Vec<bool>,
+    /// Vertex indices of each polyline from `l` statements.
+    pub lines: Vec<Vec<u32>>,
+    /// Vertex indices from `p` statements.
+    pub points: Vec<u32>,
 }
 
 impl Object {
-    pub fn new(path: String, texture: DynamicImage) -> Result<Object, Box<error::Error>> {
-        debug!("Loading object: {}", path);
-        let file_contents = fs::read_to_string(path)?;
+    /// Loads an OBJ file along with the material libraries it references,
+    /// which are looked up, like their textures, relative to the OBJ file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Object> {
+        let path = path.as_ref();
+        debug!("Loading object: {}", path.display());
+        let file_contents = fs::read(path)?;
+        Object::parse(path, &file_contents)
+    }
 
+    /// Loads an OBJ file like `new`, but from the binary cache next to it
+    /// (see `cache::path`) if that was written for the same file contents.
+    /// Otherwise the file is parsed and the cache written for next time;
+    /// failing to write it is only a warning. Material libraries are always
+    /// read again, so edits to them show up without touching the OBJ file.
+    pub fn load_cached<P: AsRef<Path>>(path: P) -> Result<Object> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let file_contents = fs::read(path)?;
+        let hash = cache::hash(&file_contents);
+        let cache_path = cache::path(path);
+        match cache::load(&cache_path, hash, |library| {
+            Object::load_library(directory, library)
+        }) {
+            Ok(Some(object)) => return Ok(object),
+            Ok(None) => {}
+            Err(error) => warn!("Ignoring object cache: {}", error),
+        }
+
+        debug!("Loading object: {}", path.display());
+        let object = Object::parse(path, &file_contents)?;
+        if let Err(error) = cache::save(&cache_path, &object, hash) {
+            warn!(
+                "Unable to write object cache {}: {}",
+                cache_path.display(),
+                error
+            );
+        }
+        Ok(object)
+    }
+
+    /// Loads the materials of the library `name` found in `directory`. A
+    /// missing library is only a warning, since exported models often
+    /// reference files that were not shipped with them.
+    fn load_library(directory: &Path, name: &str) -> Result<Vec<Material>> {
+        match mtl::load(directory.join(name), directory) {
+            Ok(materials) => Ok(materials),
+            Err(Error::Io(error)) => {
+                warn!("Skipping material library {}: {}", name, error);
+                Ok(Vec::new())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn parse(path: &Path, file_contents: &[u8]) -> Result<Object> {
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
         let mut faces: Vec<Vec<Face>> = Vec::new();
         let mut vertices: Vec<Vector3<f64>> = Vec::new();
         let mut textures: Vec<Vector3<f64>> = Vec::new();
         let mut normals: Vec<Vector3<f64>> = Vec::new();
+        let mut colors: Vec<Vector3<f64>> = Vec::new();
+        let mut material_libraries: Vec<String> = Vec::new();
+        let mut materials: Vec<Material> = Vec::new();
+        let mut face_materials: Vec<usize> = Vec::new();
+        let mut material: Option<usize> = None;
+        let mut default_material: Option<usize> = None;
+        let mut smoothing_groups: Vec<u32> = Vec::new();
+        let mut smoothing: u32 = 0;
+        let mut has_smoothing = false;
+        let mut objects: Vec<Group> = Vec::new();
+        let mut groups: Vec<Group> = Vec::new();
+        let mut object: Option<usize> = None;
+        let mut current_groups: Vec<usize> = Vec::new();
+        let mut lines: Vec<Vec<u32>> = Vec::new();
+        let mut points: Vec<u32> = Vec::new();
 
-        for line in file_contents.lines() {
-            let mut line: Vec<&str> = line.split_whitespace().collect();
-
-            if line.is_empty() {
-                continue;
-            }
+        let mut tokens: Vec<Token> = Vec::new();
+        for line in parse::lines(path, file_contents) {
+            let (location, text) = line?;
+            tokenize_into(text, &mut tokens);
+            let (line_type, line) = match tokens.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
 
-            let line_type = line.remove(0);
-
-            match line_type {
-                "f" => faces.push(Object::parse_face(&line)),
-                "vt" => match line.len() {
-                    1 => textures.push(Vector3::new(line[0].parse::<f64>().unwrap(), 0., 0.)),
-                    2 => textures.push(Vector3::new(
-                        line[0].parse::<f64>().unwrap(),
-                        line[1].parse::<f64>().unwrap(),
-                        0.,
-                    )),
-                    3 => textures.push(Vector3::new(
-                        line[0].parse::<f64>().unwrap(),
-                        line[1].parse::<f64>().unwrap(),
-                        line[2].parse::<f64>().unwrap(),
-                    )),
-                    _ => {}
-                },
-                "vn" => normals.push(Vector3::new(
-                    line[0].parse::<f64>().unwrap(),
-                    line[1].parse::<f64>().unwrap(),
-                    line[2].parse::<f64>().unwrap(),
-                )),
-                "v" => vertices.push(Vector3::new(
-                    line[0].parse::<f64>().unwrap(),
-                    line[1].parse::<f64>().unwrap(),
-                    line[2].parse::<f64>().unwrap(),
-                )),
+            match line_type.text {
+                "f" => {
+                    faces.push(Object::parse_face(
+                        &location,
+                        line,
+                        (vertices.len(), textures.len(), normals.len()),
+                    )?);
+                    let index = material.unwrap_or_else(|| {
+                        *default_material.get_or_insert_with(|| {
+                            materials.push(Material::default());
+                            materials.len() - 1
+                        })
+                    });
+                    face_materials.push(index);
+                    smoothing_groups.push(smoothing);
+                    let face = faces.len() - 1;
+                    if let Some(object) = object {
+                        objects[object].faces.push(face);
+                    }
+                    for group in &current_groups {
+                        groups[*group].faces.push(face);
+                    }
+                }
+                "l" => {
+                    let mut indices: Vec<u32> = Vec::with_capacity(line.len());
+                    for token in line {
+                        let reference = Object::parse_reference(
+                            &location,
+                            token,
+                            (vertices.len(), textures.len(), normals.len()),
+                        )?;
+                        indices.push(reference.vertex);
+                    }
+                    if indices.len() < 2 {
+                        return Err(location.error(1, "a line needs at least two vertices"));
+                    }
+                    lines.push(indices);
+                }
+                "p" => {
+                    for token in line {
+                        let reference = Object::parse_reference(
+                            &location,
+                            token,
+                            (vertices.len(), textures.len(), normals.len()),
+                        )?;
+                        points.push(reference.vertex);
+                    }
+                }
+                "o" => {
+                    let name = line.first().map(|token| rest(text, token)).unwrap_or("");
+                    object = Some(Object::find_or_add(&mut objects, name));
+                }
+                "g" => {
+                    current_groups = line
+                        .iter()
+                        .map(|token| Object::find_or_add(&mut groups, token.text))
+                        .collect();
+                    if current_groups.is_empty() {
+                        current_groups.push(Object::find_or_add(&mut groups, "default"));
+                    }
+                }
+                "s" => {
+                    has_smoothing = true;
+                    smoothing = match line.first() {
+                        Some(token) if token.text == "off" => 0,
+                        Some(token) => location.parse(token)?,
+                        None => {
+                            return Err(location.error(line_type.column, "missing smoothing group"))
+                        }
+                    }
+                }
+                "mtllib" => {
+                    for library in line {
+                        materials.extend(Object::load_library(directory, library.text)?);
+                        material_libraries.push(String::from(library.text));
+                    }
+                }
+                "usemtl" => {
+                    let name = line.first().map(|token| rest(text, token)).unwrap_or("");
+                    material = materials.iter().position(|material| material.name == name);
+                    if material.is_none() {
+                        warn!(
+                            "{}:{}: unknown material `{}`",
+                            path.display(),
+                            location.line,
+                            name
+                        );
+                    }
+                }
+                "vt" => textures.push(location.vector(line_type.column, line, 1)?),
+                "vn" => normals.push(location.vector(line_type.column, line, 3)?),
+                "v" => {
+                    vertices.push(location.vector(line_type.column, line, 3)?);
+                    if line.len() >= 6 {
+                        let color =
+                            location.vector(line_type.column, &line[line.len() - 3..], 3)?;
+                        colors.resize(vertices.len() - 1, WHITE);
+                        colors.push(Object::vertex_color(color));
+                    }
+                }
                 _ => {}
             }
         }
 
-        Ok(Object {
+        if !colors.is_empty() {
+            colors.resize(vertices.len(), WHITE);
+        }
+        if !has_smoothing {
+            smoothing_groups.iter_mut().for_each(|group| *group = 1);
+        }
+        let visible = vec![true; faces.len()];
+        let authored_normals = normals.len();
+
+        let mut object = Object {
             faces,
+            triangles: Vec::new(),
             vertices,
             normals,
+            authored_normals,
             textures,
-            texture,
-        })
+            colors,
+            tangents: Vec::new(),
+            material_libraries,
+            materials,
+            face_materials,
+            smoothing_groups,
+            objects,
+            groups,
+            visible,
+            lines,
+            points,
+        };
+        object.triangulate();
+        object.generate_normals(NormalSettings::default());
+        object.generate_tangents();
+        Ok(object)
     }
 
-    fn parse_face(line: &[&str]) -> Vec<Face> {
-        let mut face: Vec<Face> = Vec::new();
-        for reference in line {
-            let reference: Vec<&str> = reference.split('/').collect();
-            match reference.len() {
-                1 => {
-                    face.push(Face {
-                        vertex: reference[0].parse::<u32>().unwrap(),
-                        texture: 0 as u32,
-                        normal: 0 as u32,
-                    });
+    /// Fills in the normal of every face vertex without a `vn`, filling
+    /// `normals` and the `normal` index of the face vertices. Within a
+    /// smoothing group, the weighted normals of the faces sharing the vertex
+    /// position are averaged, leaving out faces at more than the crease angle
+    /// from the face being shaded. Faces with smoothing off get their flat
+    /// face normal. Normals generated by a previous call are replaced.
+    pub fn generate_normals(&mut self, settings: NormalSettings) {
+        self.normals.truncate(self.authored_normals);
+        let authored = self.authored_normals as u32;
+        for vertex in self.faces.iter_mut().flatten() {
+            if vertex.normal > authored {
+                vertex.normal = 0;
+            }
+        }
+
+        let mut face_normals: Vec<Vector3<f64>> = Vec::with_capacity(self.faces.len());
+        // Faces around each vertex position of a smoothing group, with their
+        // weighted normals.
+        let mut corners: HashMap<(u32, u32), Vec<Corner>> = HashMap::new();
+        for (index, (face, group)) in self.faces.iter().zip(&self.smoothing_groups).enumerate() {
+            let positions: Vec<Vector3<f64>> = face
+                .iter()
+                .map(|vertex| Object::lookup(&self.vertices, vertex.vertex, "vertex"))
+                .collect::<Result<_>>()
+                .unwrap_or_default();
+            if positions.len() < 3 {
+                face_normals.push(Vector3::zero());
+                continue;
+            }
+            let area_normal = Object::area_normal(&positions);
+            let normal = if area_normal.magnitude2() > f64::EPSILON {
+                area_normal.normalize()
+            } else {
+                Vector3::zero()
+            };
+            face_normals.push(normal);
+            if *group == 0 {
+                continue;
+            }
+            for (corner, vertex) in face.iter().enumerate() {
+                if vertex.normal != 0 {
+                    continue;
                 }
-                2 => {
-                    face.push(Face {
-                        vertex: reference[0].parse::<u32>().unwrap(),
-                        texture: reference[1].parse::<u32>().unwrap(),
-                        normal: 0 as u32,
-                    });
+                let weighted = match settings.weighting {
+                    NormalWeighting::Area => area_normal,
+                    NormalWeighting::Angle => normal * Object::corner_angle(&positions, corner),
+                };
+                corners
+                    .entry((vertex.vertex, *group))
+                    .or_default()
+                    .push((index, weighted));
+            }
+        }
+
+        let cos_crease = settings.crease_angle.cos();
+        let mut slots: HashMap<[u64; 3], u32> = HashMap::new();
+        let normals = &mut self.normals;
+        for (index, face) in self.faces.iter_mut().enumerate() {
+            let group = self.smoothing_groups.get(index).cloned().unwrap_or(0);
+            let face_normal = face_normals[index];
+            for vertex in face.iter_mut().filter(|vertex| vertex.normal == 0) {
+                let mut normal = face_normal;
+                if let Some(neighbours) = corners.get(&(vertex.vertex, group)) {
+                    let smooth: Vector3<f64> = neighbours
+                        .iter()
+                        .filter(|(other, _)| face_normals[*other].dot(face_normal) >= cos_crease)
+                        .map(|(_, weighted)| *weighted)
+                        .sum();
+                    if smooth.magnitude2() > f64::EPSILON {
+                        normal = smooth.normalize();
+                    }
                 }
-                3 => {
-                    face.push(Face {
-                        vertex: reference[0].parse::<u32>().unwrap(),
-                        texture: reference[1].parse::<u32>().unwrap(),
-                        normal: reference[2].parse::<u32>().unwrap(),
-                    });
+                if normal.magnitude2() <= f64::EPSILON {
+                    normal = Vector3::unit_z();
                 }
-                _ => {}
+                let key = [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()];
+                vertex.normal = *slots.entry(key).or_insert_with(|| {
+                    normals.push(normal);
+                    normals.len() as u32
+                });
             }
         }
-        face
     }
 
-    fn calc_intensity(vertices: &[Vector3<f64>]) -> f64 {
-        let n: Vector3<f64> = (vertices[2] - vertices[0]).cross(vertices[1] - vertices[0]);
-        let n = n.normalize();
+    /// Regenerates the normals that were not in the file, and the tangents
+    /// that depend on them, with `settings`.
+    pub fn with_normal_settings(mut self, settings: NormalSettings) -> Object {
+        self.generate_normals(settings);
+        self.generate_tangents();
+        self
+    }
 
-        let light_direction: Vector3<f64> = Vector3::new(0., 0., -1.);
+    /// Named parts of the object, `o` objects first and then `g` groups.
+    pub fn parts(&self) -> impl Iterator<Item = &Group> {
+        self.objects.iter().chain(&self.groups)
+    }
 
-        n.dot(light_direction)
+    /// Hides the faces of the objects and groups called `name`, returning
+    /// whether there are any.
+    pub fn hide(&mut self, name: &str) -> bool {
+        let mut found = false;
+        for part in self.objects.iter().chain(&self.groups) {
+            if part.name == name {
+                found = true;
+                for face in &part.faces {
+                    self.visible[*face] = false;
+                }
+            }
+        }
+        found
     }
 
-    pub fn render(&self, renderer: &mut impl Renderer) -> Result<bool, Box<error::Error>> {
-        let (width, height) = renderer.get_size();
-        for face in &self.faces {
-            let mut vertices: Vec<Vector3<f64>> = Vec::new();
-            let mut texture_vertices: Vec<Vector3<f64>> = Vec::new();
+    /// Draws only the faces of the objects and groups named in `names`,
+    /// returning whether they all exist.
+    pub fn show_only(&mut self, names: &[&str]) -> bool {
+        self.visible.iter_mut().for_each(|visible| *visible = false);
+        let mut found_all = true;
+        for name in names {
+            let mut found = false;
+            for part in self.objects.iter().chain(&self.groups) {
+                if part.name == *name {
+                    found = true;
+                    for face in &part.faces {
+                        self.visible[*face] = true;
+                    }
+                }
+            }
+            found_all &= found;
+        }
+        found_all
+    }
 
-            for vertex in face {
-                vertices.push(self.vertices[(vertex.vertex - 1) as usize]);
-                texture_vertices.push(self.textures[(vertex.texture - 1) as usize]);
+    /// Splits every face into triangles by ear clipping on the plane of the
+    /// face, filling `triangles`.
+    pub fn triangulate(&mut self) {
+        let vertices = &self.vertices;
+        self.triangles = self
+            .faces
+            .iter()
+            .map(|face| {
+                let positions: Vec<Vector3<f64>> = face
+                    .iter()
+                    .map(|vertex| Object::lookup(vertices, vertex.vertex, "vertex"))
+                    .collect::<Result<_>>()
+                    .unwrap_or_default();
+                if positions.len() == face.len() {
+                    triangulate(&positions)
+                } else {
+                    (1..face.len().saturating_sub(1))
+                        .map(|index| [0, index, index + 1])
+                        .collect()
+                }
+            })
+            .collect();
+    }
+
+    /// Computes per-vertex tangents from the texture coordinates, filling
+    /// `tangents` and the `tangent` index of every face vertex. Face
+    /// vertices sharing the same position, texture coordinate and normal
+    /// share a tangent.
+    pub fn generate_tangents(&mut self) {
+        let mut slots: HashMap<(u32, u32, u32), usize> = HashMap::new();
+        let mut tangents: Vec<Vector3<f64>> = Vec::new();
+        let mut bitangents: Vec<Vector3<f64>> = Vec::new();
+        let mut normals: Vec<Vector3<f64>> = Vec::new();
+        let (vertex_positions, vertex_textures, vertex_normals) =
+            (&self.vertices, &self.textures, &self.normals);
+
+        for (face, triangles) in self.faces.iter_mut().zip(&self.triangles) {
+            let lookup = |face: &Face| -> Option<(Vector3<f64>, Vector3<f64>)> {
+                Some((
+                    Object::lookup(vertex_positions, face.vertex, "vertex").ok()?,
+                    Object::lookup(vertex_textures, face.texture, "texture").ok()?,
+                ))
+            };
+            let corners: Option<Vec<(Vector3<f64>, Vector3<f64>)>> =
+                face.iter().map(lookup).collect();
+            let corners = match corners {
+                Some(corners) => corners,
+                None => {
+                    face.iter_mut().for_each(|vertex| vertex.tangent = 0);
+                    continue;
+                }
+            };
+
+            let positions: Vec<Vector3<f64>> = corners.iter().map(|corner| corner.0).collect();
+            let face_normal = Object::face_normal(&positions);
+            let (mut tangent, mut bitangent) = (Vector3::zero(), Vector3::zero());
+            for &[a, b, c] in triangles {
+                let triangle = [corners[a], corners[b], corners[c]];
+                if let Some((t, b)) = tangents_of(
+                    [triangle[0].0, triangle[1].0, triangle[2].0],
+                    [triangle[0].1, triangle[1].1, triangle[2].1],
+                ) {
+                    tangent += t;
+                    bitangent += b;
+                }
+            }
+
+            for vertex in face.iter_mut() {
+                let key = (vertex.vertex, vertex.texture, vertex.normal);
+                let slot = *slots.entry(key).or_insert_with(|| {
+                    tangents.push(Vector3::zero());
+                    bitangents.push(Vector3::zero());
+                    normals.push(Vector3::zero());
+                    tangents.len() - 1
+                });
+                tangents[slot] += tangent;
+                bitangents[slot] += bitangent;
+                normals[slot] += match Object::lookup(vertex_normals, vertex.normal, "normal") {
+                    Ok(normal) => normal,
+                    Err(_) => face_normal,
+                };
+                vertex.tangent = slot as u32 + 1;
             }
+        }
+
+        self.tangents = tangents
+            .iter()
+            .zip(bitangents)
+            .zip(normals)
+            .map(|((tangent, bitangent), normal)| orthogonal_tangent(*tangent, bitangent, normal))
+            .collect();
+    }
+
+    /// Vertex colors are usually given in `[0, 1]`, but some exporters write
+    /// bytes instead.
+    fn vertex_color(color: Vector3<f64>) -> Vector3<f64> {
+        if color.x > 1. || color.y > 1. || color.z > 1. {
+            color / 255.
+        } else {
+            color
+        }
+    }
+
+    /// Resolves a 1-based index, or one relative to the end of the `len`
+    /// elements defined so far when negative.
+    fn parse_index(
+        location: &Location,
+        token: &Token,
+        text: &str,
+        kind: &str,
+        len: usize,
+    ) -> Result<u32> {
+        let index = text.parse::<i64>().map_err(|_| {
+            location.error(
+                token.column,
+                format!("unable to parse {} index `{}`", kind, text),
+            )
+        })?;
+        let resolved = if index < 0 {
+            len as i64 + 1 + index
+        } else {
+            index
+        };
+        if resolved < 1 || resolved > len as i64 {
+            return Err(location.error(
+                token.column,
+                format!("{} index {} is out of range ({} defined)", kind, index, len),
+            ));
+        }
+        Ok(resolved as u32)
+    }
+
+    /// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` vertex reference.
+    fn parse_reference(
+        location: &Location,
+        token: &Token,
+        (vertices, textures, normals): (usize, usize, usize),
+    ) -> Result<Face> {
+        let mut parts = token.text.split('/');
+        let mut reference = [""; 3];
+        for part in reference.iter_mut() {
+            *part = parts.next().unwrap_or("");
+        }
+        if parts.next().is_some() || reference[0].is_empty() {
+            return Err(location.error(
+                token.column,
+                format!("malformed face reference `{}`", token.text),
+            ));
+        }
+        let index = |position: usize, kind: &str, len: usize| match reference[position] {
+            "" => Ok(0),
+            text => Object::parse_index(location, token, text, kind, len),
+        };
+        Ok(Face {
+            vertex: index(0, "vertex", vertices)?,
+            texture: index(1, "texture", textures)?,
+            normal: index(2, "normal", normals)?,
+            tangent: 0,
+        })
+    }
+
+    fn parse_face(
+        location: &Location,
+        line: &[Token],
+        counts: (usize, usize, usize),
+    ) -> Result<Vec<Face>> {
+        let face = line
+            .iter()
+            .map(|token| Object::parse_reference(location, token, counts))
+            .collect::<Result<Vec<Face>>>()?;
+        if face.len() < 3 {
+            return Err(location.error(1, "a face needs at least three vertices"));
+        }
+        Ok(face)
+    }
+
+    /// Index of the part called `name`, adding it if needed.
+    fn find_or_add(parts: &mut Vec<Group>, name: &str) -> usize {
+        match parts.iter().position(|part| part.name == name) {
+            Some(index) => index,
+            None => {
+                parts.push(Group {
+                    name: String::from(name),
+                    faces: Vec::new(),
+                });
+                parts.len() - 1
+            }
+        }
+    }
+
+    /// Outward facing normal of a face wound counter-clockwise.
+    fn face_normal(vertices: &[Vector3<f64>]) -> Vector3<f64> {
+        (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .normalize()
+    }
+
+    /// Interior angle of a polygon at `corner`, in radians.
+    fn corner_angle(vertices: &[Vector3<f64>], corner: usize) -> f64 {
+        let count = vertices.len();
+        let previous = vertices[(corner + count - 1) % count] - vertices[corner];
+        let next = vertices[(corner + 1) % count] - vertices[corner];
+        if previous.magnitude2() <= f64::EPSILON || next.magnitude2() <= f64::EPSILON {
+            return 0.;
+        }
+        previous.angle(next).0
+    }
 
-            let intensity = Object::calc_intensity(&vertices);
+    /// Sum of the cross products of a fan over the face, pointing along its
+    /// normal with a length of twice its area.
+    fn area_normal(vertices: &[Vector3<f64>]) -> Vector3<f64> {
+        (1..vertices.len() - 1)
+            .map(|index| (vertices[index] - vertices[0]).cross(vertices[index + 1] - vertices[0]))
+            .sum()
+    }
+
+    fn lookup(items: &[Vector3<f64>], index: u32, kind: &'static str) -> Result<Vector3<f64>> {
+        (index as usize)
+            .checked_sub(1)
+            .and_then(|index| items.get(index))
+            .cloned()
+            .ok_or(Error::InvalidIndex {
+                kind,
+                index: i64::from(index),
+                len: items.len(),
+            })
+    }
 
-            if intensity <= 0. {
+    /// Converts the visible faces, lines and points to an indexed triangle
+    /// mesh. Face vertices with the same position, texture coordinate and
+    /// normal become a single mesh vertex.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh {
+            materials: self.materials.clone(),
+            ..Mesh::default()
+        };
+        let mut slots: HashMap<(u32, u32, u32), u32> = HashMap::new();
+        let mut add = |mesh: &mut Mesh, vertex: &Face| -> u32 {
+            let key = (vertex.vertex, vertex.texture, vertex.normal);
+            *slots.entry(key).or_insert_with(|| {
+                let lookup = |items: &[Vector3<f64>], index: u32, default: Vector3<f64>| {
+                    (index as usize)
+                        .checked_sub(1)
+                        .and_then(|index| items.get(index))
+                        .cloned()
+                        .unwrap_or(default)
+                };
+                let default = Vertex::default();
+                mesh.positions
+                    .push(lookup(&self.vertices, vertex.vertex, default.position));
+                mesh.normals
+                    .push(lookup(&self.normals, vertex.normal, default.normal));
+                mesh.textures
+                    .push(lookup(&self.textures, vertex.texture, default.texture));
+                mesh.tangents.push(match vertex.tangent {
+                    0 => default.tangent,
+                    index => self.tangents[index as usize - 1],
+                });
+                if !self.colors.is_empty() {
+                    mesh.colors
+                        .push(lookup(&self.colors, vertex.vertex, default.color));
+                }
+                mesh.positions.len() as u32 - 1
+            })
+        };
+
+        for (index, (face, triangles)) in self.faces.iter().zip(&self.triangles).enumerate() {
+            if !self.visible.get(index).cloned().unwrap_or(true) {
                 continue;
             }
+            let corners: Vec<u32> = face.iter().map(|vertex| add(&mut mesh, vertex)).collect();
+            for &[a, b, c] in triangles {
+                mesh.triangles.push([corners[a], corners[b], corners[c]]);
+                mesh.triangle_materials.push(self.face_materials[index]);
+            }
+        }
+        let point = |index: u32| Face {
+            vertex: index,
+            texture: 0,
+            normal: 0,
+            tangent: 0,
+        };
+        for line in &self.lines {
+            for pair in line.windows(2) {
+                let start = add(&mut mesh, &point(pair[0]));
+                let end = add(&mut mesh, &point(pair[1]));
+                mesh.lines.push([start, end]);
+            }
+        }
+        for index in &self.points {
+            let point = add(&mut mesh, &point(*index));
+            mesh.points.push(point);
+        }
+        mesh
+    }
+}
 
-            for vertex in &mut vertices {
-                vertex.x = (vertex.x + 1.) * f64::from(width) / 2.0;
-                vertex.y = (vertex.y + 1.) * f64::from(height) / 2.0;
+/// Writes `mesh` to an OBJ file, with its materials in a `.mtl` library of
+/// the same name, see `mtl::save`.
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<()> {
+    let path = path.as_ref();
+    debug!("Saving object: {}", path.display());
+    let library = if mesh.materials.is_empty() {
+        None
+    } else {
+        let library = path.with_extension("mtl");
+        mtl::save(&mesh.materials, &library)?;
+        library
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    let mut file = BufWriter::new(File::create(path)?);
+    write(mesh, &mut file, library.as_deref())?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes `mesh` as OBJ statements, referring to the material library
+/// `library` if given. Each mesh vertex becomes a `v`, `vt` and `vn` with
+/// the same index; meshes without normals are written with smoothing off so
+/// that they stay flat shaded when loaded back.
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W, library: Option<&str>) -> Result<()> {
+    writeln!(writer, "# Written by cpuengine")?;
+    if let Some(library) = library {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+    for (index, position) in mesh.positions.iter().enumerate() {
+        match mesh.colors.get(index) {
+            Some(color) => writeln!(
+                writer,
+                "v {} {} {} {} {} {}",
+                position.x, position.y, position.z, color.x, color.y, color.z
+            )?,
+            None => writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?,
+        }
+    }
+    for texture in &mesh.textures {
+        writeln!(writer, "vt {} {}", texture.x, texture.y)?;
+    }
+    for normal in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    if mesh.normals.is_empty() {
+        writeln!(writer, "s off")?;
+    }
+
+    let reference = |index: u32| {
+        let index = index + 1;
+        match (mesh.textures.is_empty(), mesh.normals.is_empty()) {
+            (true, true) => format!("{}", index),
+            (false, true) => format!("{}/{}", index, index),
+            (true, false) => format!("{}//{}", index, index),
+            (false, false) => format!("{}/{}/{}", index, index, index),
+        }
+    };
+    let mut current = None;
+    for (index, triangle) in mesh.triangles.iter().enumerate() {
+        if library.is_some() {
+            let material = mesh
+                .triangle_materials
+                .get(index)
+                .cloned()
+                .filter(|material| *material < mesh.materials.len())
+                .unwrap_or(0);
+            if current != Some(material) {
+                writeln!(
+                    writer,
+                    "usemtl {}",
+                    mtl::name(&mesh.materials[material], material)
+                )?;
+                current = Some(material);
             }
+        }
+        writeln!(
+            writer,
+            "f {} {} {}",
+            reference(triangle[0]),
+            reference(triangle[1]),
+            reference(triangle[2])
+        )?;
+    }
+    for [start, end] in &mesh.lines {
+        writeln!(writer, "l {} {}", start + 1, end + 1)?;
+    }
+    for point in &mesh.points {
+        writeln!(writer, "p {}", point + 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::render::common::{NormalMap, NormalSpace};
+    use crate::render::png::PNG;
+    use crate::render::shader::{FlatShader, GouraudShader, PhongShader, Shader};
+    use crate::render::{Camera, Light, Renderer, Scene, ShadowSettings};
+    use image::DynamicImage;
+
+    fn parse(contents: &str) -> Result<Object> {
+        Object::parse(Path::new("test.obj"), contents.as_bytes())
+    }
+
+    #[test]
+    fn parse_object() {
+        let object = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2/1 3/1\n").unwrap();
+
+        assert_eq!(object.vertices.len(), 3);
+        assert_eq!(object.textures.len(), 1);
+        assert_eq!(object.faces.len(), 1);
+        assert_eq!(object.faces[0][2].vertex, 3);
+    }
+
+    #[test]
+    fn parse_error_location() {
+        match parse("v 0 0 0\nv 1 0 0\nv 0 1 zero\n") {
+            Err(Error::Parse { line, column, .. }) => {
+                assert_eq!(line, 3);
+                assert_eq!(column, 7);
+            }
+            _ => panic!("Expected a parse error"),
+        }
+        // Lines are read from the raw bytes, ending in `\n` or `\r\n`.
+        match Object::parse(Path::new("test.obj"), b"v 0 0 0\r\ng caf\xe9\r\n") {
+            Err(Error::Parse { line, column, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(column, 6);
+            }
+            _ => panic!("Expected a parse error"),
+        }
+    }
 
-            for index in 1..vertices.len() - 1 {
-                Triangle::new(
-                    vertices[0],
-                    vertices[index],
-                    vertices[index + 1],
-                    &self.texture,
-                    &texture_vertices,
-                    intensity,
-                )?
-                .render(renderer)?;
+    #[test]
+    fn face_index_out_of_range() {
+        match parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n") {
+            Err(Error::Parse { line, column, .. }) => {
+                assert_eq!(line, 3);
+                assert_eq!(column, 7);
             }
+            _ => panic!("Expected a parse error"),
+        }
+    }
+
+    fn render_triangle<S: Shader>(shader: &S) -> PNG {
+        let texture = white_texture();
+        let mesh = parse(
+            "v -1 -1 0\nv 1 -1 0\nv -1 1 0\nvt 0 0\n\
+             vn 0 0 1\nvn 0.8 0 0.6\nvn 0 0 1\n\
+             f 1/1/1 2/1/2 3/1/3\n",
+        )
+        .unwrap()
+        .to_mesh()
+        .with_texture(texture);
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        mesh.render_with(&mut renderer, &Scene::default(), shader)
+            .unwrap();
+        renderer
+    }
+
+    #[test]
+    fn render_with_vertex_normals() {
+        for renderer in &[
+            render_triangle(&GouraudShader),
+            render_triangle(&PhongShader),
+        ] {
+            let lit = renderer.get_pixel(1, 1).unwrap();
+            let angled = renderer.get_pixel(17, 1).unwrap();
+            assert!(lit[0] > 240);
+            assert!(angled[0] < 200);
         }
-        Ok(true)
+
+        let flat = render_triangle(&FlatShader);
+        assert_eq!(flat.get_pixel(17, 1), Some([255, 255, 255]));
+    }
+
+    #[test]
+    fn render_shadows() {
+        let texture = white_texture();
+        let mut mesh = parse(
+            "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+             v -0.25 -0.25 0.5\nv 0.25 -0.25 0.5\nv 0.25 0.25 0.5\nv -0.25 0.25 0.5\n\
+             vt 0 0\nf 1/1 2/1 3/1 4/1\nf 5/1 6/1 7/1 8/1\n",
+        )
+        .unwrap()
+        .to_mesh()
+        .with_texture(texture);
+        mesh.materials[0].specular = Vector3::zero();
+        let scene = Scene::unlit(Camera::default())
+            .with_light(Light::directional(Vector3::new(-1., 0., -1.)))
+            .with_shadows(ShadowSettings {
+                resolution: 256,
+                pcf_radius: 0,
+                ..ShadowSettings::default()
+            });
+
+        let mut renderer: PNG = Renderer::new(100, 100);
+        mesh.render_with(&mut renderer, &scene, &PhongShader)
+            .unwrap();
+
+        let lit = renderer.get_pixel(80, 50).unwrap();
+        let shadowed = renderer.get_pixel(25, 50).unwrap();
+        let occluder = renderer.get_pixel(50, 50).unwrap();
+        assert!(lit[0] > 150);
+        assert_eq!(shadowed, [0, 0, 0]);
+        assert_eq!(occluder, lit);
+    }
+
+    fn white_texture() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            1,
+            1,
+            image::Rgb([255, 255, 255]),
+        ))
+    }
+
+    #[test]
+    fn generate_tangents() {
+        let object = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvt 0 1\nvt 0 0\n\
+             f 1/1 2/2 3/3 4/4\nf 1/5 3/3 4/6\n",
+        )
+        .unwrap();
+
+        let first = &object.faces[0];
+        assert_eq!(
+            object.tangents[first[0].tangent as usize - 1],
+            Vector4::new(1., 0., 0., 1.)
+        );
+        assert_eq!(first[2].tangent, object.faces[1][1].tangent);
+    }
+
+    #[test]
+    fn render_normal_map() {
+        let contents = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                        vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\n";
+        let scene = Scene::unlit(Camera::default())
+            .with_light(Light::directional(Vector3::new(-1., 0., 0.)));
+        let render = |normal_map: NormalMap| {
+            let mesh = parse(contents)
+                .unwrap()
+                .to_mesh()
+                .with_texture(white_texture())
+                .with_normal_map(normal_map);
+            let mut renderer: PNG = Renderer::new(10, 10);
+            mesh.render_with(&mut renderer, &scene, &PhongShader)
+                .unwrap();
+            renderer.get_pixel(5, 5).unwrap()
+        };
+        let map = |color: [u8; 3], space: NormalSpace| NormalMap {
+            image: DynamicImage::ImageRgb8(image::RgbImage::from_pixel(2, 2, image::Rgb(color))),
+            space,
+        };
+
+        assert_eq!(
+            render(map([128, 128, 255], NormalSpace::Tangent)),
+            [0, 0, 0]
+        );
+        assert!(render(map([255, 128, 128], NormalSpace::Object))[0] > 250);
+        assert!(render(map([255, 128, 128], NormalSpace::Tangent))[0] > 250);
+        assert_eq!(render(map([0, 128, 128], NormalSpace::Tangent)), [0, 0, 0]);
+    }
+
+    #[test]
+    fn render_materials() {
+        let directory =
+            std::env::temp_dir().join(format!("cpuengine-render-materials-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("colors.mtl"),
+            "newmtl red\nKd 1 0 0\nillum 0\nnewmtl blue\nKd 0 0 1\nillum 0\n",
+        )
+        .unwrap();
+        let path = directory.join("quads.obj");
+        fs::write(
+            &path,
+            "mtllib colors.mtl missing.mtl\n\
+             v -1 -1 0\nv 0 -1 0\nv 1 -1 0\nv -1 1 0\nv 0 1 0\nv 1 1 0\n\
+             usemtl red\nf 1 2 5 4\nusemtl blue\nf 2 3 6 5\nusemtl unknown\nf 1 2 4\n",
+        )
+        .unwrap();
+
+        let object = Object::new(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(object.materials.len(), 3);
+        assert_eq!(object.face_materials, vec![0, 1, 2]);
+        assert_eq!(object.materials[2].name, "");
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        object
+            .to_mesh()
+            .render(&mut renderer, &Scene::default())
+            .unwrap();
+        assert_eq!(renderer.get_pixel(5, 15), Some([255, 0, 0]));
+        assert_eq!(renderer.get_pixel(15, 15), Some([0, 0, 255]));
+    }
+
+    #[test]
+    fn render_vertex_colors() {
+        let object =
+            parse("v -1 -1 0 1 0 0\nv 1 -1 0 0 255 0\nv -1 1 0\nv 1 1 0\nf 1 2 3\nf 2 4 3\n")
+                .unwrap();
+        assert_eq!(
+            object.colors,
+            vec![
+                Vector3::new(1., 0., 0.),
+                Vector3::new(0., 1., 0.),
+                Vector3::new(1., 1., 1.),
+                Vector3::new(1., 1., 1.),
+            ]
+        );
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        object
+            .to_mesh()
+            .render(&mut renderer, &Scene::default())
+            .unwrap();
+        let red = renderer.get_pixel(1, 1).unwrap();
+        let green = renderer.get_pixel(18, 1).unwrap();
+        assert!(red[0] > 200 && red[1] < 50 && red[2] < 50);
+        assert!(green[1] > 200 && green[0] < 50 && green[2] < 50);
+        let white = renderer.get_pixel(18, 18).unwrap();
+        assert!(white.iter().all(|channel| *channel > 200));
+    }
+
+    #[test]
+    fn parse_references() {
+        let object = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\n\
+             f 1//1 2//1 3//1\nf -3/-1/-1 -2/ -1\n",
+        )
+        .unwrap();
+
+        let first = &object.faces[0][1];
+        assert_eq!((first.vertex, first.texture, first.normal), (2, 0, 1));
+        let second: Vec<(u32, u32)> = object.faces[1]
+            .iter()
+            .map(|face| (face.vertex, face.texture))
+            .collect();
+        assert_eq!(second, vec![(1, 1), (2, 0), (3, 0)]);
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 2 3\n").is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf /1 2 3\n").is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1/1/1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn parse_parts() {
+        let mut object = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\no body\ng left shared\nf 1 2 3\ng right shared\ns 2\nf 1 3 4\n\
+             o wire frame\ns off\nf 1 2 4\nl 1 2/1 3\np 4 -1\n",
+        )
+        .unwrap();
+
+        let names: Vec<&str> = object.parts().map(|part| part.name.as_str()).collect();
+        assert_eq!(names, vec!["body", "wire frame", "left", "shared", "right"]);
+        assert_eq!(object.objects[0].faces, vec![0, 1]);
+        assert_eq!(object.groups[1].faces, vec![0, 1, 2]);
+        assert_eq!(object.smoothing_groups, vec![0, 2, 0]);
+        assert_eq!(object.lines, vec![vec![1, 2, 3]]);
+        assert_eq!(object.points, vec![4, 4]);
+
+        assert!(object.hide("left"));
+        assert_eq!(object.visible, vec![false, true, true]);
+        assert!(object.show_only(&["body"]));
+        assert_eq!(object.visible, vec![true, true, false]);
+        assert!(!object.show_only(&["wire frame", "missing"]));
+        assert_eq!(object.visible, vec![false, false, true]);
+        assert!(!object.hide("missing"));
+    }
+
+    #[test]
+    fn smoothing_group_normals() {
+        let contents = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 1 0 -1\n\
+                        s 1\nf 1 2 3\nf 2 4 3\ns off\nf 1 3 4\n";
+        let smooth = NormalSettings {
+            crease_angle: Deg(180.),
+            ..NormalSettings::default()
+        };
+        let object = parse(contents).unwrap().with_normal_settings(smooth);
+
+        let shared = &object.faces[0][1];
+        assert_eq!(shared.normal, object.faces[1][0].normal);
+        let normal = object.normals[shared.normal as usize - 1];
+        let expected = Vector3::new(1., 0., 1.).normalize();
+        assert!((normal - expected).magnitude() < 1e-9);
+        assert_ne!(object.faces[0][0].normal, object.faces[1][1].normal);
+        let flat = object.normals[object.faces[2][0].normal as usize - 1];
+        let expected = Vector3::new(-1., 1., -1.).normalize();
+        assert!((flat - expected).magnitude() < 1e-9);
+        assert!(object.faces[2]
+            .iter()
+            .all(|face| face.normal == object.faces[2][0].normal));
+
+        let creased = parse(contents).unwrap();
+        let normal = creased.normals[creased.faces[0][1].normal as usize - 1];
+        assert_eq!(normal, Vector3::unit_z());
+        assert_eq!(creased.normals.len(), 3);
+    }
+
+    #[test]
+    fn normal_weighting() {
+        let contents = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 10\nvn 0 0 1\n\
+                        f 1 2 3\nf 1 3 4\nf 1//1 2//1 3//1\n";
+        let settings = |weighting| NormalSettings {
+            weighting,
+            crease_angle: Deg(180.),
+        };
+        let corner = |object: &Object| object.normals[object.faces[0][0].normal as usize - 1];
+
+        let area = parse(contents)
+            .unwrap()
+            .with_normal_settings(settings(NormalWeighting::Area));
+        assert!((corner(&area) - Vector3::new(10., 0., 1.).normalize()).magnitude() < 1e-9);
+
+        let angle = parse(contents)
+            .unwrap()
+            .with_normal_settings(settings(NormalWeighting::Angle));
+        assert!((corner(&angle) - Vector3::new(1., 0., 1.).normalize()).magnitude() < 1e-9);
+        assert_eq!(angle.authored_normals, 1);
+        assert_eq!(angle.faces[2][0].normal, 1);
+    }
+
+    #[test]
+    fn render_lines() {
+        let object = parse("v -1 0 0 1 0 0\nv 1 0 0\nv 0 0.5 0\nl 1 2\np 3\n").unwrap();
+
+        let mut renderer: PNG = Renderer::new(20, 20);
+        object
+            .to_mesh()
+            .render(&mut renderer, &Scene::default())
+            .unwrap();
+        assert_eq!(renderer.get_pixel(0, 10), Some([255, 0, 0]));
+        assert_eq!(renderer.get_pixel(10, 10), Some([255, 127, 127]));
+        assert_eq!(renderer.get_pixel(10, 15), Some([255, 255, 255]));
+        assert_eq!(renderer.get_pixel(10, 12), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn render_concave_face() {
+        // An L shape whose first corner is next to the notch, where a fan
+        // would spill over into the notch.
+        let object =
+            parse("v 0 1 0\nv -1 1 0\nv -1 -1 0\nv 1 -1 0\nv 1 0 0\nv 0 0 0\nf 1 2 3 4 5 6\n")
+                .unwrap();
+
+        assert_eq!(object.triangles[0].len(), 4);
+        let mut renderer: PNG = Renderer::new(40, 40);
+        object
+            .to_mesh()
+            .render(&mut renderer, &Scene::default())
+            .unwrap();
+        assert_eq!(renderer.get_pixel(36, 22), Some([0, 0, 0]));
+        assert_eq!(renderer.get_pixel(10, 30), Some([255, 255, 255]));
+        assert_eq!(renderer.get_pixel(30, 10), Some([255, 255, 255]));
+    }
+
+    #[test]
+    fn convert_to_mesh() {
+        let mut object = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\n\
+             o quad\nf 1/1 2/1 3/1 4/1\no corner\nf 1/2 2/1 3/1\nl 1 4\np 4\n",
+        )
+        .unwrap();
+
+        let mesh = object.to_mesh();
+        // Line and point vertices have no normal, so they are not shared
+        // with the faces.
+        assert_eq!(mesh.positions.len(), 7);
+        assert_eq!(mesh.normals.len(), 7);
+        assert!(mesh.colors.is_empty());
+        assert_eq!(mesh.triangles.len(), 3);
+        assert_eq!(mesh.triangle_materials, vec![0, 0, 0]);
+        assert_eq!(mesh.triangles[2], [4, 1, 2]);
+        assert_eq!(mesh.lines, vec![[5, 6]]);
+        assert_eq!(mesh.points, vec![6]);
+
+        object.hide("quad");
+        assert_eq!(object.to_mesh().triangles.len(), 1);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mesh = parse(
+            "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 1 1 0 0 0 1\nv 0 1 0 1 1 1\nvt 0 0\nvt 1 1\n\
+             f 1/1 2/1 3/2\nf 1/1 3/2 4/2\nf 1/2 2/1 3/1\nl 1 4\np 4\n",
+        )
+        .unwrap()
+        .to_mesh();
+        let mut bytes = Vec::new();
+        write(&mesh, &mut bytes, None).unwrap();
+        let written = parse(std::str::from_utf8(&bytes).unwrap())
+            .unwrap()
+            .to_mesh();
+
+        assert_eq!(written.positions, mesh.positions);
+        assert_eq!(written.normals, mesh.normals);
+        assert_eq!(written.textures, mesh.textures);
+        assert_eq!(written.colors, mesh.colors);
+        assert_eq!(written.triangles, mesh.triangles);
+        assert_eq!(written.lines, mesh.lines);
+        assert_eq!(written.points, mesh.points);
+
+        // Without normals, smoothing is turned off to keep the mesh flat.
+        let flat = Mesh::new(mesh.positions[..3].to_vec(), vec![[0, 1, 2]]);
+        let mut bytes = Vec::new();
+        write(&flat, &mut bytes, None).unwrap();
+        let written = parse(std::str::from_utf8(&bytes).unwrap()).unwrap();
+        assert_eq!(written.smoothing_groups, vec![0]);
+        assert_eq!(written.faces[0][2].vertex, 3);
+    }
+
+    #[test]
+    fn save_materials() {
+        let directory =
+            std::env::temp_dir().join(format!("cpuengine-save-materials-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let texture = DynamicImage::ImageRgb8(image::RgbImage::from_fn(2, 2, |x, y| {
+            image::Rgb([x as u8 * 255, y as u8 * 255, 0])
+        }));
+        let mut mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n")
+            .unwrap()
+            .to_mesh();
+        mesh.materials[0] = Material::default()
+            .with_diffuse(Vector3::new(1., 0.5, 0.))
+            .with_diffuse_map(texture.clone());
+        let path = directory.join("triangle.obj");
+        save(&mesh, &path).unwrap();
+
+        let object = Object::new(&path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(object.materials.len(), 1);
+        assert_eq!(object.materials[0].name, "material_0");
+        assert_eq!(object.materials[0].diffuse, Vector3::new(1., 0.5, 0.));
+        assert_eq!(
+            object.materials[0]
+                .diffuse_map
+                .as_ref()
+                .unwrap()
+                .raw_pixels(),
+            texture.raw_pixels()
+        );
+        assert_eq!(object.face_materials, vec![0]);
     }
 }
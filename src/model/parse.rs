@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use cgmath::Vector3;
+
+use crate::error::{Error, Result};
+
+/// A whitespace separated word of a line along with its 1-based column.
+pub(crate) struct Token<'a> {
+    pub column: usize,
+    pub text: &'a str,
+}
+
+/// Position in the file being parsed, used to give errors some context.
+pub(crate) struct Location<'a> {
+    pub path: &'a Path,
+    pub line: usize,
+}
+
+impl<'a> Location<'a> {
+    pub fn error<M: Into<String>>(&self, column: usize, message: M) -> Error {
+        Error::parse(self.path, self.line, column, message)
+    }
+
+    pub fn parse<T: FromStr>(&self, token: &Token) -> Result<T> {
+        token.text.parse::<T>().map_err(|_| {
+            self.error(
+                token.column,
+                format!("unable to parse `{}` as a number", token.text),
+            )
+        })
+    }
+
+    pub fn vector(&self, column: usize, tokens: &[Token], required: usize) -> Result<Vector3<f64>> {
+        if tokens.len() < required {
+            return Err(self.error(
+                column,
+                format!("expected {} values, found {}", required, tokens.len()),
+            ));
+        }
+
+        let mut vector = Vector3::new(0., 0., 0.);
+        for (index, token) in tokens.iter().take(3).enumerate() {
+            vector[index] = self.parse(token)?;
+        }
+        Ok(vector)
+    }
+}
+
+pub(crate) fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    tokenize_into(line, &mut tokens);
+    tokens
+}
+
+/// Splits `line` into `tokens`, reusing their storage from the previous
+/// line.
+pub(crate) fn tokenize_into<'a>(line: &'a str, tokens: &mut Vec<Token<'a>>) {
+    tokens.clear();
+    tokens.extend(line.split_whitespace().map(|text| Token {
+        column: text.as_ptr() as usize - line.as_ptr() as usize + 1,
+        text,
+    }));
+}
+
+/// Lines of a file read as bytes, without their `\n` or `\r\n` ending,
+/// checked to be valid UTF-8 one at a time.
+pub(crate) fn lines<'a>(
+    path: &'a Path,
+    file_contents: &'a [u8],
+) -> impl Iterator<Item = Result<(Location<'a>, &'a str)>> {
+    file_contents
+        .split(|byte| *byte == b'\n')
+        .enumerate()
+        .map(move |(index, line)| {
+            let location = Location {
+                path,
+                line: index + 1,
+            };
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            match std::str::from_utf8(line) {
+                Ok(line) => Ok((location, line)),
+                Err(error) => Err(location.error(error.valid_up_to() + 1, "invalid UTF-8")),
+            }
+        })
+}
+
+/// The remainder of `line` from `token` on, for names that may contain
+/// spaces.
+pub(crate) fn rest<'a>(line: &'a str, token: &Token) -> &'a str {
+    line[token.column - 1..].trim_end()
+}
@@ -0,0 +1,755 @@
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::Lines;
+
+use cgmath::Vector3;
+use log::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::geometry::common::triangulate;
+use crate::model::mesh::Mesh;
+use crate::model::parse::{tokenize, Location, Token};
+
+/// Type of a property value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::Char),
+            "uchar" | "uint8" => Some(Scalar::UChar),
+            "short" | "int16" => Some(Scalar::Short),
+            "ushort" | "uint16" => Some(Scalar::UShort),
+            "int" | "int32" => Some(Scalar::Int),
+            "uint" | "uint32" => Some(Scalar::UInt),
+            "float" | "float32" => Some(Scalar::Float),
+            "double" | "float64" => Some(Scalar::Double),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Scalar::Char => "char",
+            Scalar::UChar => "uchar",
+            Scalar::Short => "short",
+            Scalar::UShort => "ushort",
+            Scalar::Int => "int",
+            Scalar::UInt => "uint",
+            Scalar::Float => "float",
+            Scalar::Double => "double",
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Char | Scalar::UChar => 1,
+            Scalar::Short | Scalar::UShort => 2,
+            Scalar::Int | Scalar::UInt | Scalar::Float => 4,
+            Scalar::Double => 8,
+        }
+    }
+
+    /// Value that a color stored as this type reaches at full intensity.
+    fn full_intensity(self) -> f64 {
+        match self {
+            Scalar::Char => 127.,
+            Scalar::UChar => 255.,
+            Scalar::Short => 32767.,
+            Scalar::UShort => 65535.,
+            Scalar::Int => 2_147_483_647.,
+            Scalar::UInt => 4_294_967_295.,
+            Scalar::Float | Scalar::Double => 1.,
+        }
+    }
+
+    /// Decodes a value from the first `size` bytes of `bytes`.
+    fn decode(self, bytes: &[u8], big_endian: bool) -> f64 {
+        macro_rules! decode {
+            ($type:ty) => {{
+                let bytes = bytes[..self.size()].try_into().unwrap();
+                if big_endian {
+                    <$type>::from_be_bytes(bytes)
+                } else {
+                    <$type>::from_le_bytes(bytes)
+                }
+            }};
+        }
+        match self {
+            Scalar::Char => f64::from(decode!(i8)),
+            Scalar::UChar => f64::from(decode!(u8)),
+            Scalar::Short => f64::from(decode!(i16)),
+            Scalar::UShort => f64::from(decode!(u16)),
+            Scalar::Int => f64::from(decode!(i32)),
+            Scalar::UInt => f64::from(decode!(u32)),
+            Scalar::Float => f64::from(decode!(f32)),
+            Scalar::Double => decode!(f64),
+        }
+    }
+
+    /// Encodes `value`, converted to this type, as `size` bytes.
+    fn encode(self, value: f64, big_endian: bool) -> Vec<u8> {
+        macro_rules! encode {
+            ($type:ty) => {{
+                let value = value as $type;
+                if big_endian {
+                    value.to_be_bytes().to_vec()
+                } else {
+                    value.to_le_bytes().to_vec()
+                }
+            }};
+        }
+        match self {
+            Scalar::Char => encode!(i8),
+            Scalar::UChar => encode!(u8),
+            Scalar::Short => encode!(i16),
+            Scalar::UShort => encode!(u16),
+            Scalar::Int => encode!(i32),
+            Scalar::UInt => encode!(u32),
+            Scalar::Float => encode!(f32),
+            Scalar::Double => encode!(f64),
+        }
+    }
+
+    /// Formats `value`, converted to this type, for an ASCII file.
+    fn format(self, value: f64) -> String {
+        match self {
+            Scalar::Float => format!("{}", value as f32),
+            Scalar::Double => format!("{}", value),
+            _ => format!("{}", value as i64),
+        }
+    }
+}
+
+enum Property {
+    Scalar {
+        name: String,
+        kind: Scalar,
+    },
+    List {
+        name: String,
+        count: Scalar,
+        kind: Scalar,
+    },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// How the data following the header of a PLY file is stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ascii",
+            Encoding::BinaryLittleEndian => "binary_little_endian",
+            Encoding::BinaryBigEndian => "binary_big_endian",
+        }
+    }
+}
+
+/// The data following the header, read one value at a time.
+enum Body<'a> {
+    Ascii {
+        path: &'a Path,
+        lines: std::iter::Enumerate<Lines<'a>>,
+        /// Line number of the first body line.
+        start: usize,
+        line: usize,
+        tokens: Vec<Token<'a>>,
+    },
+    Binary {
+        path: &'a Path,
+        bytes: &'a [u8],
+        big_endian: bool,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, kind: Scalar) -> Result<f64> {
+        match self {
+            Body::Ascii {
+                path,
+                lines,
+                start,
+                line,
+                tokens,
+            } => {
+                while tokens.is_empty() {
+                    let (index, text) = lines
+                        .next()
+                        .ok_or_else(|| Error::format(*path, "unexpected end of file"))?;
+                    *line = *start + index;
+                    *tokens = tokenize(text);
+                    tokens.reverse();
+                }
+                let token = tokens.pop().unwrap();
+                Location { path, line: *line }.parse(&token)
+            }
+            Body::Binary {
+                path,
+                bytes,
+                big_endian,
+            } => {
+                if bytes.len() < kind.size() {
+                    return Err(Error::format(*path, "unexpected end of file"));
+                }
+                let value = kind.decode(bytes, *big_endian);
+                *bytes = &bytes[kind.size()..];
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Loads a PLY file in any of its encodings. Vertex positions, normals
+/// (`nx`, `ny`, `nz`), colors (`red`, `green`, `blue`) and texture
+/// coordinates (`s` and `t`, or `u` and `v`) are read, as are polygon faces
+/// of any size and edges. Files without faces or edges load as a point
+/// cloud.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    debug!("Loading PLY: {}", path.display());
+    let file_contents = fs::read(path)?;
+    parse(path, &file_contents)
+}
+
+pub(crate) fn parse(path: &Path, file_contents: &[u8]) -> Result<Mesh> {
+    let header_end = find_header_end(file_contents)
+        .ok_or_else(|| Error::format(path, "missing `end_header`"))?;
+    let header = std::str::from_utf8(&file_contents[..header_end])
+        .map_err(|_| Error::format(path, "PLY header is not valid UTF-8"))?;
+    let (encoding, elements) = parse_header(path, header)?;
+    let header_lines = header.lines().count();
+
+    let data = &file_contents[header_end..];
+    let mut body = match encoding {
+        Encoding::Ascii => Body::Ascii {
+            path,
+            lines: std::str::from_utf8(data)
+                .map_err(|_| Error::format(path, "ASCII PLY is not valid UTF-8"))?
+                .lines()
+                .enumerate(),
+            start: header_lines + 1,
+            line: header_lines,
+            tokens: Vec::new(),
+        },
+        Encoding::BinaryLittleEndian | Encoding::BinaryBigEndian => Body::Binary {
+            path,
+            bytes: data,
+            big_endian: encoding == Encoding::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = Mesh::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            "edge" => read_edges(&mut body, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        read_property(&mut body, property)?;
+                    }
+                }
+            }
+        }
+    }
+    if mesh.triangles.is_empty() && mesh.lines.is_empty() {
+        mesh.points = (0..mesh.positions.len() as u32).collect();
+    }
+    Ok(mesh)
+}
+
+/// Writes `mesh` to a binary little-endian PLY file.
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<()> {
+    let path = path.as_ref();
+    debug!("Saving PLY: {}", path.display());
+    let mut file = BufWriter::new(File::create(path)?);
+    write(mesh, &mut file, Encoding::BinaryLittleEndian)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes `mesh` as PLY: its vertices with the attributes it has, colors
+/// stored as bytes, its triangles as faces and its lines as edges. Points
+/// are only kept for meshes with neither, which load back as a point cloud.
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W, encoding: Encoding) -> Result<()> {
+    let mut properties = vec![
+        ("x", Scalar::Float),
+        ("y", Scalar::Float),
+        ("z", Scalar::Float),
+    ];
+    if !mesh.normals.is_empty() {
+        properties.extend(&[
+            ("nx", Scalar::Float),
+            ("ny", Scalar::Float),
+            ("nz", Scalar::Float),
+        ]);
+    }
+    if !mesh.colors.is_empty() {
+        properties.extend(&[
+            ("red", Scalar::UChar),
+            ("green", Scalar::UChar),
+            ("blue", Scalar::UChar),
+        ]);
+    }
+    if !mesh.textures.is_empty() {
+        properties.extend(&[("s", Scalar::Float), ("t", Scalar::Float)]);
+    }
+    let point_cloud = mesh.triangles.is_empty() && mesh.lines.is_empty();
+    if !point_cloud && !mesh.points.is_empty() {
+        warn!("PLY cannot store points alongside faces or edges, skipping them");
+    }
+
+    writeln!(writer, "ply\nformat {} 1.0", encoding.name())?;
+    writeln!(writer, "comment written by cpuengine")?;
+    writeln!(writer, "element vertex {}", mesh.positions.len())?;
+    for (name, kind) in &properties {
+        writeln!(writer, "property {} {}", kind.name(), name)?;
+    }
+    if !mesh.triangles.is_empty() {
+        writeln!(writer, "element face {}", mesh.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+    }
+    if !mesh.lines.is_empty() {
+        writeln!(writer, "element edge {}", mesh.lines.len())?;
+        writeln!(writer, "property int vertex1\nproperty int vertex2")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for (index, position) in mesh.positions.iter().enumerate() {
+        let mut values = vec![position.x, position.y, position.z];
+        if let Some(normal) = mesh.normals.get(index) {
+            values.extend(&[normal.x, normal.y, normal.z]);
+        }
+        if let Some(color) = mesh.colors.get(index) {
+            let byte = |channel: f64| (channel.clamp(0., 1.) * 255.).round();
+            values.extend(&[byte(color.x), byte(color.y), byte(color.z)]);
+        }
+        if let Some(texture) = mesh.textures.get(index) {
+            values.extend(&[texture.x, texture.y]);
+        }
+        let kinds = properties.iter().map(|(_, kind)| *kind);
+        write_values(writer, encoding, kinds.zip(values))?;
+    }
+    for triangle in &mesh.triangles {
+        let kinds = [Scalar::UChar, Scalar::Int, Scalar::Int, Scalar::Int];
+        let values = [
+            3.,
+            f64::from(triangle[0]),
+            f64::from(triangle[1]),
+            f64::from(triangle[2]),
+        ];
+        write_values(
+            writer,
+            encoding,
+            kinds.iter().cloned().zip(values.iter().cloned()),
+        )?;
+    }
+    for line in &mesh.lines {
+        let values = [f64::from(line[0]), f64::from(line[1])];
+        write_values(
+            writer,
+            encoding,
+            values.iter().map(|value| (Scalar::Int, *value)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the values of one element, on a line of their own in ASCII files.
+fn write_values<W: Write>(
+    writer: &mut W,
+    encoding: Encoding,
+    values: impl Iterator<Item = (Scalar, f64)>,
+) -> Result<()> {
+    match encoding {
+        Encoding::Ascii => {
+            let values: Vec<String> = values.map(|(kind, value)| kind.format(value)).collect();
+            writeln!(writer, "{}", values.join(" "))?;
+        }
+        Encoding::BinaryLittleEndian | Encoding::BinaryBigEndian => {
+            for (kind, value) in values {
+                writer.write_all(&kind.encode(value, encoding == Encoding::BinaryBigEndian))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Offset of the first byte after the `end_header` line.
+fn find_header_end(file_contents: &[u8]) -> Option<usize> {
+    const END: &[u8] = b"end_header";
+    let start = file_contents
+        .windows(END.len())
+        .position(|window| window == END)?;
+    let newline = file_contents[start..]
+        .iter()
+        .position(|byte| *byte == b'\n')?;
+    Some(start + newline + 1)
+}
+
+fn parse_header(path: &Path, header: &str) -> Result<(Encoding, Vec<Element>)> {
+    let mut encoding: Option<Encoding> = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (index, line) in header.lines().enumerate() {
+        let tokens = tokenize(line);
+        let location = Location {
+            path,
+            line: index + 1,
+        };
+        if index == 0 {
+            if tokens.len() != 1 || tokens[0].text != "ply" {
+                return Err(location.error(1, "not a PLY file"));
+            }
+            continue;
+        }
+        let (statement, arguments) = match tokens.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        let missing = || {
+            location.error(
+                statement.column,
+                format!("missing value for `{}`", statement.text),
+            )
+        };
+        match statement.text {
+            "format" => {
+                let format = arguments.first().ok_or_else(missing)?;
+                encoding = Some(match format.text {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    _ => {
+                        return Err(location
+                            .error(format.column, format!("unknown format `{}`", format.text)))
+                    }
+                });
+            }
+            "element" => {
+                if arguments.len() < 2 {
+                    return Err(missing());
+                }
+                elements.push(Element {
+                    name: String::from(arguments[0].text),
+                    count: location.parse(&arguments[1])?,
+                    properties: Vec::new(),
+                });
+            }
+            "property" => {
+                let element = elements.last_mut().ok_or_else(|| {
+                    location.error(statement.column, "`property` before any `element`")
+                })?;
+                let scalar = |token: &Token| {
+                    Scalar::parse(token.text).ok_or_else(|| {
+                        location.error(token.column, format!("unknown type `{}`", token.text))
+                    })
+                };
+                let property = match arguments {
+                    [list, count, kind, name] if list.text == "list" => Property::List {
+                        name: String::from(name.text),
+                        count: scalar(count)?,
+                        kind: scalar(kind)?,
+                    },
+                    [kind, name] => Property::Scalar {
+                        name: String::from(name.text),
+                        kind: scalar(kind)?,
+                    },
+                    _ => return Err(location.error(statement.column, "malformed property")),
+                };
+                element.properties.push(property);
+            }
+            "comment" | "obj_info" | "end_header" => {}
+            _ => {
+                return Err(location.error(
+                    statement.column,
+                    format!("unknown statement `{}`", statement.text),
+                ))
+            }
+        }
+    }
+
+    let encoding = encoding.ok_or_else(|| Error::format(path, "missing `format`"))?;
+    Ok((encoding, elements))
+}
+
+/// Reads a property, returning the values of a list.
+fn read_property(body: &mut Body, property: &Property) -> Result<Vec<f64>> {
+    match property {
+        Property::Scalar { kind, .. } => Ok(vec![body.read(*kind)?]),
+        Property::List { count, kind, .. } => {
+            let count = body.read(*count)?;
+            (0..count as usize).map(|_| body.read(*kind)).collect()
+        }
+    }
+}
+
+fn read_vertices(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<()> {
+    // Slot of each property in the vertex attributes, in the order
+    // position, normal, color and texture coordinate.
+    let slot = |name: &str| match name {
+        "x" => Some(0),
+        "y" => Some(1),
+        "z" => Some(2),
+        "nx" => Some(3),
+        "ny" => Some(4),
+        "nz" => Some(5),
+        "red" => Some(6),
+        "green" => Some(7),
+        "blue" => Some(8),
+        "s" | "u" | "texture_u" => Some(9),
+        "t" | "v" | "texture_v" => Some(10),
+        _ => None,
+    };
+    let mut present = [false; 11];
+    for property in &element.properties {
+        if let Property::Scalar { name, .. } = property {
+            if let Some(slot) = slot(name) {
+                present[slot] = true;
+            }
+        }
+    }
+    let has = |slots: &[usize]| slots.iter().all(|slot| present[*slot]);
+    if !has(&[0, 1, 2]) {
+        warn!("PLY vertices are missing some of x, y and z");
+    }
+
+    for _ in 0..element.count {
+        let mut values = [0.; 11];
+        for property in &element.properties {
+            let value = read_property(body, property)?;
+            if let Property::Scalar { name, kind } = property {
+                if let Some(slot) = slot(name) {
+                    values[slot] = if (6..9).contains(&slot) {
+                        value[0] / kind.full_intensity()
+                    } else {
+                        value[0]
+                    };
+                }
+            }
+        }
+        mesh.positions
+            .push(Vector3::new(values[0], values[1], values[2]));
+        if has(&[3, 4, 5]) {
+            mesh.normals
+                .push(Vector3::new(values[3], values[4], values[5]));
+        }
+        if has(&[6, 7, 8]) {
+            mesh.colors
+                .push(Vector3::new(values[6], values[7], values[8]));
+        }
+        if has(&[9, 10]) {
+            mesh.textures.push(Vector3::new(values[9], values[10], 0.));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<()> {
+    for _ in 0..element.count {
+        let mut face: Vec<u32> = Vec::new();
+        for property in &element.properties {
+            let values = read_property(body, property)?;
+            match property {
+                Property::List { name, .. }
+                    if name == "vertex_indices" || name == "vertex_index" =>
+                {
+                    face = values
+                        .iter()
+                        .map(|index| vertex_index(mesh, *index))
+                        .collect::<Result<_>>()?;
+                }
+                _ => {}
+            }
+        }
+        if face.len() < 3 {
+            continue;
+        }
+        let positions: Vec<Vector3<f64>> = face
+            .iter()
+            .map(|index| mesh.positions[*index as usize])
+            .collect();
+        for [a, b, c] in triangulate(&positions) {
+            mesh.triangles.push([face[a], face[b], face[c]]);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `index`, read from a face or edge, refers to a vertex.
+fn vertex_index(mesh: &Mesh, index: f64) -> Result<u32> {
+    if index < 0. || index >= mesh.positions.len() as f64 {
+        return Err(Error::InvalidIndex {
+            kind: "vertex",
+            index: index as i64,
+            len: mesh.positions.len(),
+        });
+    }
+    Ok(index as u32)
+}
+
+fn read_edges(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<()> {
+    for _ in 0..element.count {
+        let mut edge: [Option<u32>; 2] = [None, None];
+        for property in &element.properties {
+            let values = read_property(body, property)?;
+            let end = match property {
+                Property::Scalar { name, .. } if name == "vertex1" => 0,
+                Property::Scalar { name, .. } if name == "vertex2" => 1,
+                _ => continue,
+            };
+            edge[end] = Some(vertex_index(mesh, values[0])?);
+        }
+        if let [Some(start), Some(end)] = edge {
+            mesh.lines.push([start, end]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &[u8]) -> Result<Mesh> {
+        super::parse(Path::new("test.ply"), contents)
+    }
+
+    #[test]
+    fn parse_ascii() {
+        let mesh = parse(
+            b"ply\nformat ascii 1.0\ncomment a colored quad\n\
+              element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+              property uchar red\nproperty uchar green\nproperty uchar blue\n\
+              property float s\nproperty float t\n\
+              element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+              0 0 0 255 0 0 0 0\n1 0 0 0 255 0 1 0\n1 1 0 0 0 255 1 1\n0 1 0 255 255 255 0 1\n\
+              4 0 1 2 3\n",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.positions[2], Vector3::new(1., 1., 0.));
+        assert_eq!(mesh.colors[1], Vector3::new(0., 1., 0.));
+        assert_eq!(mesh.textures[3], Vector3::new(0., 1., 0.));
+        assert!(mesh.normals.is_empty());
+        assert_eq!(mesh.triangles.len(), 2);
+        assert!(mesh.points.is_empty());
+    }
+
+    #[test]
+    fn parse_binary() {
+        let mut bytes = b"ply\nformat binary_big_endian 1.0\n\
+              element vertex 2\nproperty double x\nproperty double y\nproperty double z\n\
+              property float nx\nproperty float ny\nproperty float nz\n\
+              property int confidence\nend_header\n"
+            .to_vec();
+        for vertex in &[[1., 2., 3.], [4., 5., 6.]] {
+            for value in vertex {
+                bytes.extend(&f64::to_be_bytes(*value));
+            }
+            for value in &[0f32, 0., 1.] {
+                bytes.extend(&value.to_be_bytes());
+            }
+            bytes.extend(&7i32.to_be_bytes());
+        }
+        let mesh = parse(&bytes).unwrap();
+
+        assert_eq!(
+            mesh.positions,
+            vec![Vector3::new(1., 2., 3.), Vector3::new(4., 5., 6.)]
+        );
+        assert_eq!(mesh.normals[1], Vector3::unit_z());
+        assert!(mesh.triangles.is_empty());
+        assert_eq!(mesh.points, vec![0, 1]);
+
+        match parse(&bytes[..bytes.len() - 1]) {
+            Err(Error::Format { .. }) => {}
+            _ => panic!("expected a format error"),
+        }
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mut mesh = Mesh::new(
+            vec![
+                Vector3::new(0., 0., 0.),
+                Vector3::new(1., 0., 0.),
+                Vector3::new(0., 1., 0.),
+            ],
+            vec![[0, 1, 2]],
+        );
+        mesh.normals = vec![Vector3::unit_z(); 3];
+        mesh.colors = vec![
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 1., 0.),
+            Vector3::new(0., 0., 1.),
+        ];
+        mesh.textures = vec![
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0.5, 0., 0.),
+            Vector3::new(0., 0.25, 0.),
+        ];
+        mesh.lines = vec![[2, 0]];
+
+        for encoding in &[
+            Encoding::Ascii,
+            Encoding::BinaryLittleEndian,
+            Encoding::BinaryBigEndian,
+        ] {
+            let mut bytes = Vec::new();
+            write(&mesh, &mut bytes, *encoding).unwrap();
+            let written = parse(&bytes).unwrap();
+
+            assert_eq!(written.positions, mesh.positions);
+            assert_eq!(written.normals, mesh.normals);
+            assert_eq!(written.colors, mesh.colors);
+            assert_eq!(written.textures, mesh.textures);
+            assert_eq!(written.triangles, mesh.triangles);
+            assert_eq!(written.lines, mesh.lines);
+            assert!(written.points.is_empty());
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                      element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+        match parse(format!("{}zero\n3 0 0 0\n", header).as_bytes()) {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (8, 1)),
+            _ => panic!("expected a parse error"),
+        }
+        match parse(format!("{}0\n3 0 0 1\n", header).as_bytes()) {
+            Err(Error::InvalidIndex { index, len, .. }) => assert_eq!((index, len), (1, 1)),
+            _ => panic!("expected an invalid index"),
+        }
+        match parse(b"ply\nformat ascii 1.0\nproperty float x\nend_header\n") {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (3, 1)),
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
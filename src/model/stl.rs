@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use cgmath::{InnerSpace, Vector3};
+use log::debug;
+
+use crate::error::{Error, Result};
+use crate::geometry::common::triangulate;
+use crate::model::mesh::Mesh;
+use crate::model::parse::{tokenize, Location};
+
+/// Size of the header of a binary STL file, followed by the triangle count.
+const HEADER: usize = 80;
+/// Size of a triangle in a binary STL file: a normal, three vertices and a
+/// 16-bit attribute.
+const TRIANGLE: usize = 50;
+
+/// Loads an STL file, either ASCII or binary. Vertices at the same position
+/// are welded together, and the facet normals are only used to fix the
+/// winding of triangles listed the wrong way round, so the mesh is shaded
+/// flat.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    debug!("Loading STL: {}", path.display());
+    let file_contents = fs::read(path)?;
+    parse(path, &file_contents)
+}
+
+/// Writes `mesh` to a binary STL file.
+pub fn save<P: AsRef<Path>>(mesh: &Mesh, path: P) -> Result<()> {
+    let path = path.as_ref();
+    debug!("Saving STL: {}", path.display());
+    let mut file = BufWriter::new(File::create(path)?);
+    write(mesh, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Writes the triangles of `mesh` as binary STL, with the face normal of
+/// each. STL has no other vertex attributes, materials, lines or points, so
+/// those are dropped.
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W) -> Result<()> {
+    // The header must not start with `solid`, which would mark it as ASCII.
+    let mut header = b"binary STL written by cpuengine".to_vec();
+    header.resize(HEADER, 0);
+    writer.write_all(&header)?;
+    writer.write_all(&(mesh.triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in &mesh.triangles {
+        let a = mesh.position(triangle[0])?;
+        let b = mesh.position(triangle[1])?;
+        let c = mesh.position(triangle[2])?;
+        let normal = (b - a).cross(c - a);
+        let normal = if normal.magnitude2() > 0. {
+            normal.normalize()
+        } else {
+            normal
+        };
+        for vector in &[normal, a, b, c] {
+            for value in &[vector.x, vector.y, vector.z] {
+                writer.write_all(&(*value as f32).to_le_bytes())?;
+            }
+        }
+        writer.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+pub(crate) fn parse(path: &Path, file_contents: &[u8]) -> Result<Mesh> {
+    // Binary files may also start with `solid`, so the size is checked first.
+    if is_binary(file_contents) || !file_contents.starts_with(b"solid") {
+        parse_binary(path, file_contents)
+    } else {
+        let text = std::str::from_utf8(file_contents)
+            .map_err(|_| Error::format(path, "ASCII STL is not valid UTF-8"))?;
+        parse_ascii(path, text)
+    }
+}
+
+/// Whether the size of the file matches the triangle count in its header.
+fn is_binary(file_contents: &[u8]) -> bool {
+    if file_contents.len() < HEADER + 4 {
+        return false;
+    }
+    let count = u32::from_le_bytes(file_contents[HEADER..HEADER + 4].try_into().unwrap());
+    file_contents.len() as u64 == (HEADER + 4) as u64 + u64::from(count) * TRIANGLE as u64
+}
+
+fn parse_binary(path: &Path, file_contents: &[u8]) -> Result<Mesh> {
+    if file_contents.len() < HEADER + 4 {
+        return Err(Error::format(path, "binary STL is missing its header"));
+    }
+    let count = u32::from_le_bytes(file_contents[HEADER..HEADER + 4].try_into().unwrap()) as usize;
+    let triangles = &file_contents[HEADER + 4..];
+    if triangles.len() / TRIANGLE < count {
+        return Err(Error::format(
+            path,
+            format!(
+                "binary STL declares {} triangles but only holds {}",
+                count,
+                triangles.len() / TRIANGLE
+            ),
+        ));
+    }
+
+    let mut builder = Builder::default();
+    for triangle in triangles.chunks_exact(TRIANGLE).take(count) {
+        let vector = |index: usize| {
+            let float = |offset: usize| {
+                let start = index * 12 + offset * 4;
+                f64::from(f32::from_le_bytes(
+                    triangle[start..start + 4].try_into().unwrap(),
+                ))
+            };
+            Vector3::new(float(0), float(1), float(2))
+        };
+        builder.add(vector(0), &[vector(1), vector(2), vector(3)]);
+    }
+    Ok(builder.mesh)
+}
+
+fn parse_ascii(path: &Path, file_contents: &str) -> Result<Mesh> {
+    let mut builder = Builder::default();
+    let mut normal: Option<Vector3<f64>> = None;
+    let mut facet: Vec<Vector3<f64>> = Vec::new();
+
+    for (index, line) in file_contents.lines().enumerate() {
+        let mut tokens = tokenize(line);
+        if tokens.is_empty() {
+            continue;
+        }
+
+        let location = Location {
+            path,
+            line: index + 1,
+        };
+        let statement = tokens.remove(0);
+        match statement.text {
+            "solid" | "endsolid" | "outer" | "endloop" => {}
+            "facet" => {
+                if normal.is_some() {
+                    return Err(location.error(statement.column, "`facet` inside a facet"));
+                }
+                normal = Some(match tokens.split_first() {
+                    Some((kind, values)) if kind.text == "normal" => {
+                        location.vector(kind.column, values, 3)?
+                    }
+                    _ => Vector3::new(0., 0., 0.),
+                });
+            }
+            "vertex" => {
+                if normal.is_none() {
+                    return Err(location.error(statement.column, "`vertex` outside a facet"));
+                }
+                facet.push(location.vector(statement.column, &tokens, 3)?);
+            }
+            "endfacet" => {
+                let normal = normal.take().ok_or_else(|| {
+                    location.error(statement.column, "`endfacet` outside a facet")
+                })?;
+                if facet.len() < 3 {
+                    return Err(location.error(
+                        statement.column,
+                        format!(
+                            "a facet needs at least three vertices, found {}",
+                            facet.len()
+                        ),
+                    ));
+                }
+                builder.add(normal, &facet);
+                facet.clear();
+            }
+            _ => {
+                return Err(location.error(
+                    statement.column,
+                    format!("unknown statement `{}`", statement.text),
+                ))
+            }
+        }
+    }
+    if normal.is_some() {
+        return Err(Error::format(path, "unexpected end of file inside a facet"));
+    }
+    Ok(builder.mesh)
+}
+
+/// Collects facets into a mesh, welding vertices by position.
+#[derive(Default)]
+struct Builder {
+    mesh: Mesh,
+    slots: HashMap<[u64; 3], u32>,
+}
+
+impl Builder {
+    fn add(&mut self, normal: Vector3<f64>, facet: &[Vector3<f64>]) {
+        let mut corners: Vec<u32> = Vec::with_capacity(facet.len());
+        for position in facet {
+            // Adding zero turns -0 into 0, so that both weld together.
+            let position = position + Vector3::new(0., 0., 0.);
+            let key = [
+                position.x.to_bits(),
+                position.y.to_bits(),
+                position.z.to_bits(),
+            ];
+            let positions = &mut self.mesh.positions;
+            corners.push(*self.slots.entry(key).or_insert_with(|| {
+                positions.push(position);
+                positions.len() as u32 - 1
+            }));
+        }
+
+        for [a, b, c] in triangulate(facet) {
+            let mut triangle = [corners[a], corners[b], corners[c]];
+            if triangle[0] == triangle[1]
+                || triangle[1] == triangle[2]
+                || triangle[0] == triangle[2]
+            {
+                continue;
+            }
+            let winding = (facet[b] - facet[a]).cross(facet[c] - facet[a]);
+            if winding.dot(normal) < 0. {
+                triangle.swap(1, 2);
+            }
+            self.mesh.triangles.push(triangle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "solid cube corner\n\
+        facet normal 0 0 1\n  outer loop\n\
+        vertex 0 0 0\n    vertex 1 0 0\n    vertex 0 1 0\n\
+        endloop\nendfacet\n\
+        facet normal 0 0 1\n  outer loop\n\
+        vertex 1 0 0\n    vertex 0 1 0\n    vertex 1 1 -0\n\
+        endloop\nendfacet\n\
+        endsolid cube corner\n";
+
+    fn binary(facets: &[[[f32; 3]; 4]]) -> Vec<u8> {
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(HEADER, 0);
+        bytes.extend(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            for value in facet.iter().flatten() {
+                bytes.extend(&value.to_le_bytes());
+            }
+            bytes.extend(&[0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_ascii() {
+        let mesh = parse(Path::new("test.stl"), ASCII.as_bytes()).unwrap();
+
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [1, 3, 2]]);
+        assert!(mesh.normals.is_empty());
+    }
+
+    #[test]
+    fn parse_binary() {
+        let bytes = binary(&[
+            [[0., 0., 1.], [0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+        ]);
+        let mesh = parse(Path::new("test.stl"), &bytes).unwrap();
+
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [1, 3, 2]]);
+
+        match parse(Path::new("test.stl"), &bytes[..bytes.len() - 1]) {
+            Err(Error::Format { .. }) => {}
+            _ => panic!("expected a format error"),
+        }
+    }
+
+    #[test]
+    fn write_binary() {
+        let mut mesh = parse(Path::new("test.stl"), ASCII.as_bytes()).unwrap();
+        mesh.triangles.push([0, 0, 1]);
+        let mut bytes = Vec::new();
+        write(&mesh, &mut bytes).unwrap();
+
+        assert_eq!(bytes.len(), HEADER + 4 + 3 * TRIANGLE);
+        let float = |start: usize| f32::from_le_bytes(bytes[start..start + 4].try_into().unwrap());
+        let normal = HEADER + 4;
+        assert_eq!(
+            [float(normal), float(normal + 4), float(normal + 8)],
+            [0., 0., 1.]
+        );
+        let written = parse(Path::new("test.stl"), &bytes).unwrap();
+        assert_eq!(written.positions, mesh.positions);
+        assert_eq!(written.triangles, vec![[0, 1, 2], [1, 3, 2]]);
+
+        mesh.triangles.push([0, 1, 4]);
+        match write(&mesh, &mut Vec::new()) {
+            Err(Error::InvalidIndex { index: 4, .. }) => {}
+            _ => panic!("expected an invalid index"),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        match parse(
+            Path::new("test.stl"),
+            b"solid a\nfacet normal 0 0 1\nvertex 0 zero 0\n",
+        ) {
+            Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (3, 10)),
+            _ => panic!("expected a parse error"),
+        }
+        match parse(
+            Path::new("test.stl"),
+            b"solid a\nfacet normal 0 0 1\nvertex 0 0 0\n",
+        ) {
+            Err(Error::Format { .. }) => {}
+            _ => panic!("expected a format error"),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use cgmath::{
+    ortho, perspective, Deg, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4,
+};
+
+/// How the view volume is flattened onto the image plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Perspective projection with a vertical field of view.
+    Perspective { fovy: Deg<f64> },
+    /// Parallel projection showing `height` world units vertically.
+    Orthographic { height: f64 },
+}
+
+/// Model-view-projection setup used to place vertices on screen.
+///
+/// Screen space has its origin in the bottom left corner of the image. Depth
+/// is mapped to `[0, 1]` with larger values closer to the eye, matching the
+/// depth test done by the renderers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub eye: Point3<f64>,
+    pub target: Point3<f64>,
+    pub up: Vector3<f64>,
+    pub projection: Projection,
+    pub near: f64,
+    pub far: f64,
+    /// Transform from object space into world space.
+    pub model: Matrix4<f64>,
+}
+
+impl Default for Camera {
+    /// Looks down the negative z axis at the `[-1, 1]` cube, the space the
+    /// bundled models are authored in.
+    fn default() -> Camera {
+        Camera {
+            eye: Point3::new(0., 0., 3.),
+            target: Point3::new(0., 0., 0.),
+            up: Vector3::unit_y(),
+            projection: Projection::Orthographic { height: 2. },
+            near: 0.1,
+            far: 100.,
+            model: Matrix4::identity(),
+        }
+    }
+}
+
+impl Camera {
+    /// Perspective camera at `eye` looking at `target`.
+    pub fn new(eye: Point3<f64>, target: Point3<f64>, up: Vector3<f64>) -> Camera {
+        Camera {
+            eye,
+            target,
+            up,
+            projection: Projection::Perspective { fovy: Deg(45.) },
+            ..Camera::default()
+        }
+    }
+
+    /// Normalized direction the camera is looking in.
+    pub fn direction(&self) -> Vector3<f64> {
+        (self.target - self.eye).normalize()
+    }
+
+    pub fn view(&self) -> Matrix4<f64> {
+        Matrix4::look_at(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self, aspect: f64) -> Matrix4<f64> {
+        match self.projection {
+            Projection::Perspective { fovy } => perspective(fovy, aspect, self.near, self.far),
+            Projection::Orthographic { height } => {
+                let (top, right) = (height / 2., height * aspect / 2.);
+                ortho(-right, right, -top, top, self.near, self.far)
+            }
+        }
+    }
+
+    /// Maps normalized device coordinates onto a `width` by `height` image.
+    pub fn viewport(width: u32, height: u32) -> Matrix4<f64> {
+        let (width, height) = (f64::from(width), f64::from(height));
+        Matrix4::new(
+            width / 2.,
+            0.,
+            0.,
+            0.,
+            0.,
+            height / 2.,
+            0.,
+            0.,
+            0.,
+            0.,
+            -0.5,
+            0.,
+            width / 2.,
+            height / 2.,
+            0.5,
+            1.,
+        )
+    }
+
+    /// Combined model, view and projection transform into clip space.
+    pub fn clip_transform(&self, width: u32, height: u32) -> Matrix4<f64> {
+        let aspect = f64::from(width) / f64::from(height);
+        self.projection(aspect) * self.view() * self.model
+    }
+
+    /// Combined transform from object space straight into screen space.
+    pub fn transform(&self, width: u32, height: u32) -> Matrix4<f64> {
+        Camera::viewport(width, height) * self.clip_transform(width, height)
+    }
+
+    /// Projects `vertex` into screen space with `transform` from
+    /// `Camera::transform`. Returns `None` for points behind the eye.
+    pub fn project(transform: &Matrix4<f64>, vertex: Vector3<f64>) -> Option<Vector3<f64>> {
+        let clip: Vector4<f64> = transform * vertex.extend(1.);
+        if clip.w <= f64::EPSILON {
+            return None;
+        }
+        Some(clip.truncate() / clip.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_camera_maps_unit_cube_to_screen() {
+        let transform = Camera::default().transform(100, 50);
+
+        let corner = Camera::project(&transform, Vector3::new(-2., -1., 0.)).unwrap();
+        assert!(corner.x.abs() < 1e-9);
+        assert!(corner.y.abs() < 1e-9);
+
+        let center = Camera::project(&transform, Vector3::new(0., 0., 0.)).unwrap();
+        assert!((center.x - 50.).abs() < 1e-9);
+        assert!((center.y - 25.).abs() < 1e-9);
+
+        let near = Camera::project(&transform, Vector3::new(0., 0., 1.)).unwrap();
+        assert!(near.z > center.z);
+    }
+
+    #[test]
+    fn perspective_camera() {
+        let camera = Camera::new(
+            Point3::new(0., 0., 5.),
+            Point3::new(0., 0., 0.),
+            Vector3::unit_y(),
+        );
+        let transform = camera.transform(100, 100);
+
+        let center = Camera::project(&transform, Vector3::new(0., 0., 0.)).unwrap();
+        assert!((center.x - 50.).abs() < 1e-9);
+
+        let far = Camera::project(&transform, Vector3::new(1., 0., -5.)).unwrap();
+        let close = Camera::project(&transform, Vector3::new(1., 0., 0.)).unwrap();
+        assert!(far.x < close.x);
+
+        assert!(Camera::project(&transform, Vector3::new(0., 0., 10.)).is_none());
+    }
+}
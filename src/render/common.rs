@@ -1,16 +1,129 @@
-// pub fn color_vec(colors: Vec<[u8; 3]>, intensity: f64) -> Vec<[u8; 3]> {
-//     let mut rendered_color: Vec<[u8; 3]> = Vec::new();
+use std::path::{Path, PathBuf};
 
-//     for color in colors {
-//         rendered_color.push([
-//             (intensity * f64::from(color[0])) as u8,
-//             (intensity * f64::from(color[1])) as u8,
-//             (intensity * f64::from(color[2])) as u8,
-//         ]);
-//     }
+use cgmath::{InnerSpace, Vector3};
+use image::{open, DynamicImage, GenericImageView};
+use log::{debug, warn};
 
-//     rendered_color
-// }
+use crate::error::Result;
+
+/// Space the normals stored in a normal map are expressed in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalSpace {
+    /// Relative to the object, independent of the surface orientation.
+    Object,
+    /// Relative to the surface, along the tangent, bitangent and normal.
+    Tangent,
+}
+
+/// How texture coordinates outside `[0, 1]` are brought onto a texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    /// Tiles the texture.
+    Repeat,
+    /// Tiles the texture, mirroring every other tile.
+    MirroredRepeat,
+    /// Stretches the texels along the edges.
+    Clamp,
+}
+
+impl Wrap {
+    /// Maps the texture coordinate `t` into `[0, 1]`.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Wrap::Repeat => t - t.floor(),
+            Wrap::MirroredRepeat => {
+                let t = t.rem_euclid(2.);
+                if t > 1. {
+                    2. - t
+                } else {
+                    t
+                }
+            }
+            Wrap::Clamp => t.clamp(0., 1.),
+        }
+    }
+}
+
+/// Texture storing a shading normal per texel, each axis mapped from
+/// `[-1, 1]` to `[0, 255]`.
+#[derive(Clone)]
+pub struct NormalMap {
+    pub image: DynamicImage,
+    pub space: NormalSpace,
+}
+
+impl NormalMap {
+    /// Opens a normal map, flipping it to match the diffuse texture.
+    pub fn open<P: AsRef<Path>>(path: P, space: NormalSpace) -> Result<NormalMap> {
+        debug!("Loading normal map: {}", path.as_ref().display());
+        Ok(NormalMap {
+            image: open(path)?.flipv(),
+            space,
+        })
+    }
+
+    /// Looks for the normal map shipped next to a diffuse texture, e.g.
+    /// `head_nm_tangent.tga` or `head_nm.tga` for `head_diffuse.tga`.
+    /// Tangent space maps are preferred.
+    pub fn find<P: AsRef<Path>>(diffuse: P) -> Option<(PathBuf, NormalSpace)> {
+        let candidates = [
+            ("_nm_tangent", NormalSpace::Tangent),
+            ("_nm", NormalSpace::Object),
+        ];
+        candidates
+            .iter()
+            .find_map(|(suffix, space)| Some((sibling(diffuse.as_ref(), suffix)?, *space)))
+    }
+
+    /// Decoded, normalized normal at texture coordinate `coord`.
+    pub fn normal(&self, coord: Vector3<f64>) -> Vector3<f64> {
+        let texel = sample(&self.image, coord);
+        let axis = |channel: u8| f64::from(channel) / 255. * 2. - 1.;
+        Vector3::new(axis(texel[0]), axis(texel[1]), axis(texel[2])).normalize()
+    }
+}
+
+/// Texture scaling the specular strength and exponent of a material per
+/// texel, read from the first channel.
+#[derive(Clone)]
+pub struct SpecularMap {
+    pub image: DynamicImage,
+}
+
+impl SpecularMap {
+    /// Opens a specular map, flipping it to match the diffuse texture.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SpecularMap> {
+        debug!("Loading specular map: {}", path.as_ref().display());
+        Ok(SpecularMap {
+            image: open(path)?.flipv(),
+        })
+    }
+
+    /// Looks for the specular map shipped next to a diffuse texture, e.g.
+    /// `head_spec.tga` for `head_diffuse.tga`.
+    pub fn find<P: AsRef<Path>>(diffuse: P) -> Option<PathBuf> {
+        sibling(diffuse.as_ref(), "_spec")
+    }
+
+    /// Specular factor in `[0, 1]` at texture coordinate `coord`.
+    pub fn factor(&self, coord: Vector3<f64>) -> f64 {
+        f64::from(sample(&self.image, coord)[0]) / 255.
+    }
+}
+
+/// Existing file next to `diffuse` with its `_diffuse` suffix replaced by
+/// `suffix`.
+fn sibling(diffuse: &Path, suffix: &str) -> Option<PathBuf> {
+    let stem = diffuse.file_stem()?.to_str()?;
+    let stem = stem.trim_end_matches("_diffuse");
+    let extension = diffuse.extension()?.to_str()?;
+    let path = diffuse.with_file_name(format!("{}{}.{}", stem, suffix, extension));
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
 
 pub fn color(color: [u8; 3], intensity: f64) -> [u8; 3] {
     [
@@ -19,3 +132,52 @@ pub fn color(color: [u8; 3], intensity: f64) -> [u8; 3] {
         (intensity * f64::from(color[2])) as u8,
     ]
 }
+
+/// Scales each channel of `color` by the matching channel of `light`.
+pub fn shade(color: [u8; 3], light: Vector3<f64>) -> [u8; 3] {
+    [
+        (light.x * f64::from(color[0])) as u8,
+        (light.y * f64::from(color[1])) as u8,
+        (light.z * f64::from(color[2])) as u8,
+    ]
+}
+
+/// Adds the specular `light` on top of an already shaded `color`.
+pub fn highlight(color: [u8; 3], light: Vector3<f64>) -> [u8; 3] {
+    let add = |channel: u8, light: f64| (f64::from(channel) + light * 255.) as u8;
+    [
+        add(color[0], light.x),
+        add(color[1], light.y),
+        add(color[2], light.z),
+    ]
+}
+
+/// Looks up the texel at texture coordinate `coord`, with `(0, 0)` in the
+/// first row of `texture`.
+pub fn sample(texture: &DynamicImage, coord: Vector3<f64>) -> [u8; 3] {
+    // Coordinates of 1 round to the texel past the last one, so they are
+    // pulled back onto it.
+    let texel = |t: f64, size: u32| {
+        let texel = (t * f64::from(size)).round() as u32;
+        if t <= 1. {
+            texel.min(size.saturating_sub(1))
+        } else {
+            texel
+        }
+    };
+    let x = texel(coord.x, texture.width());
+    let y = texel(coord.y, texture.height());
+
+    if texture.in_bounds(x, y) {
+        let pixel = texture.get_pixel(x, y);
+        return [pixel[0], pixel[1], pixel[2]];
+    }
+    warn!(
+        "Requested color outside texture bounds: {}, {}, {}, {}",
+        x,
+        y,
+        texture.width(),
+        texture.height(),
+    );
+    [255, 255, 255]
+}
@@ -0,0 +1,73 @@
+use cgmath::Vector3;
+
+use crate::error::Result;
+use crate::render::tile::{Tile, Tiled};
+use crate::render::Renderer;
+
+/// Depth only render target. Keeps the largest (closest) depth written to
+/// each pixel and discards colors.
+pub struct DepthBuffer {
+    pub width: u32,
+    pub height: u32,
+    zindex: Vec<f64>,
+}
+
+impl Renderer for DepthBuffer {
+    fn new(width: u32, height: u32) -> DepthBuffer {
+        DepthBuffer {
+            width,
+            height,
+            zindex: vec![f64::NEG_INFINITY; width as usize * height as usize],
+        }
+    }
+
+    fn set_pixel(&mut self, pixel: Vector3<f64>, _: [u8; 3]) {
+        self.test(pixel);
+    }
+
+    fn render(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+impl Tiled for DepthBuffer {
+    fn tiles(&mut self, size: u32) -> Vec<Tile<'_>> {
+        Tile::split(self.width, size, self.rows_mut(), std::iter::empty())
+    }
+}
+
+impl DepthBuffer {
+    /// Rows of depths, bottom row first.
+    pub(crate) fn rows_mut(&mut self) -> impl Iterator<Item = &mut [f64]> {
+        self.zindex.chunks_mut(self.width.max(1) as usize)
+    }
+
+    fn index(&self, x: f64, y: f64) -> Option<usize> {
+        if x > f64::from(self.width) - 1. || x < 0. || y > f64::from(self.height) - 1. || y < 0. {
+            return None;
+        }
+        Some((x as usize) + (y as usize) * self.width as usize)
+    }
+
+    /// Depth test: stores `pixel.z` and returns `true` if it is closer than
+    /// what was previously written at `(pixel.x, pixel.y)`.
+    pub fn test(&mut self, pixel: Vector3<f64>) -> bool {
+        match self.index(pixel.x, pixel.y) {
+            Some(index) if self.zindex[index] < pixel.z => {
+                self.zindex[index] = pixel.z;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Closest depth written at `(x, y)`, or negative infinity if nothing
+    /// was. Coordinates outside the buffer return `None`.
+    pub fn depth(&self, x: f64, y: f64) -> Option<f64> {
+        self.index(x, y).map(|index| self.zindex[index])
+    }
+}
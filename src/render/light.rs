@@ -0,0 +1,208 @@
+use cgmath::{Deg, InnerSpace, Rad, Vector3};
+
+/// Constant, linear and quadratic distance falloff of a positional light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Default for Attenuation {
+    fn default() -> Attenuation {
+        Attenuation {
+            constant: 1.,
+            linear: 0.,
+            quadratic: 0.,
+        }
+    }
+}
+
+impl Attenuation {
+    pub fn at(&self, distance: f64) -> f64 {
+        let falloff = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        if falloff <= 0. {
+            return 1.;
+        }
+        1. / falloff
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Parallel rays travelling along `direction`, like sunlight.
+    Directional { direction: Vector3<f64> },
+    /// Shines in every direction from `position`.
+    Point {
+        position: Vector3<f64>,
+        attenuation: Attenuation,
+    },
+    /// Shines from `position` along `direction`. Full intensity inside the
+    /// `inner` cone angle, fading out towards the `outer` one.
+    Spot {
+        position: Vector3<f64>,
+        direction: Vector3<f64>,
+        inner: Deg<f64>,
+        outer: Deg<f64>,
+        attenuation: Attenuation,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear RGB color, each channel in `[0, 1]`.
+    pub color: Vector3<f64>,
+    pub intensity: f64,
+}
+
+impl Light {
+    fn new(kind: LightKind) -> Light {
+        Light {
+            kind,
+            color: Vector3::new(1., 1., 1.),
+            intensity: 1.,
+        }
+    }
+
+    pub fn directional(direction: Vector3<f64>) -> Light {
+        Light::new(LightKind::Directional {
+            direction: direction.normalize(),
+        })
+    }
+
+    pub fn point(position: Vector3<f64>) -> Light {
+        Light::new(LightKind::Point {
+            position,
+            attenuation: Attenuation::default(),
+        })
+    }
+
+    pub fn spot(
+        position: Vector3<f64>,
+        direction: Vector3<f64>,
+        inner: Deg<f64>,
+        outer: Deg<f64>,
+    ) -> Light {
+        Light::new(LightKind::Spot {
+            position,
+            direction: direction.normalize(),
+            inner,
+            outer,
+            attenuation: Attenuation::default(),
+        })
+    }
+
+    pub fn with_color(mut self, color: Vector3<f64>) -> Light {
+        self.color = color;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Light {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sets the falloff of point and spot lights. Directional lights are
+    /// left unchanged.
+    pub fn with_attenuation(mut self, falloff: Attenuation) -> Light {
+        match &mut self.kind {
+            LightKind::Point { attenuation, .. } | LightKind::Spot { attenuation, .. } => {
+                *attenuation = falloff
+            }
+            LightKind::Directional { .. } => {}
+        }
+        self
+    }
+
+    /// Returns the normalized direction from the world space `position`
+    /// towards the light along with the light color reaching it, or `None`
+    /// if the light does not reach it at all.
+    pub fn incident(&self, position: Vector3<f64>) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let (to_light, factor) = match self.kind {
+            LightKind::Directional { direction } => (-direction, 1.),
+            LightKind::Point {
+                position: source,
+                attenuation,
+            } => {
+                let offset = source - position;
+                let distance = offset.magnitude();
+                (offset / distance, attenuation.at(distance))
+            }
+            LightKind::Spot {
+                position: source,
+                direction,
+                inner,
+                outer,
+                attenuation,
+            } => {
+                let offset = source - position;
+                let distance = offset.magnitude();
+                let to_light = offset / distance;
+                let cone = spot_factor((-to_light).dot(direction), inner, outer);
+                (to_light, attenuation.at(distance) * cone)
+            }
+        };
+
+        if factor <= 0. || !to_light.x.is_finite() {
+            return None;
+        }
+        Some((to_light, self.color * (self.intensity * factor)))
+    }
+}
+
+/// Smooth falloff between the inner and outer cone of a spot light, given
+/// the cosine of the angle between the spot direction and the lit point.
+fn spot_factor(cos_angle: f64, inner: Deg<f64>, outer: Deg<f64>) -> f64 {
+    let cos_inner = Rad::from(inner).0.cos();
+    let cos_outer = Rad::from(outer).0.cos();
+    if cos_inner <= cos_outer {
+        return if cos_angle >= cos_outer { 1. } else { 0. };
+    }
+    let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directional_light() {
+        let light = Light::directional(Vector3::new(0., 0., -2.)).with_intensity(0.5);
+        let (to_light, color) = light.incident(Vector3::new(4., 5., 6.)).unwrap();
+
+        assert_eq!(to_light, Vector3::new(0., 0., 1.));
+        assert_eq!(color, Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn point_light_attenuation() {
+        let light = Light::point(Vector3::new(0., 2., 0.)).with_attenuation(Attenuation {
+            constant: 0.,
+            linear: 0.,
+            quadratic: 1.,
+        });
+        let (to_light, color) = light.incident(Vector3::new(0., 0., 0.)).unwrap();
+
+        assert_eq!(to_light, Vector3::new(0., 1., 0.));
+        assert!((color.x - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = Light::spot(
+            Vector3::new(0., 0., 5.),
+            Vector3::new(0., 0., -1.),
+            Deg(10.),
+            Deg(20.),
+        );
+
+        let (_, center) = light.incident(Vector3::new(0., 0., 0.)).unwrap();
+        assert!((center.x - 1.).abs() < 1e-12);
+        assert!(light.incident(Vector3::new(5., 0., 0.)).is_none());
+
+        let (_, edge) = light.incident(Vector3::new(1.3, 0., 0.)).unwrap();
+        assert!(edge.x > 0. && edge.x < 1.);
+    }
+}
@@ -0,0 +1,113 @@
+use cgmath::Vector3;
+use image::DynamicImage;
+
+use crate::render::common::{sample, NormalMap, SpecularMap, Wrap};
+
+/// Material every object falls back to, see `Material::new`.
+pub(crate) static DEFAULT: Material = Material::new(String::new());
+
+/// How a surface responds to light, following the parameters of a Wavefront
+/// `.mtl` material.
+#[derive(Clone)]
+pub struct Material {
+    pub name: String,
+    /// Fraction of the ambient light reflected (`Ka`).
+    pub ambient: Vector3<f64>,
+    /// Diffuse color, multiplied with the diffuse map (`Kd`).
+    pub diffuse: Vector3<f64>,
+    /// Color and strength of specular highlights (`Ks`).
+    pub specular: Vector3<f64>,
+    /// Blinn-Phong exponent; higher values give smaller, sharper highlights
+    /// (`Ns`).
+    pub shininess: f64,
+    /// Opacity, multiplied with the alpha map (`d`, or `1 - Tr`). There is no
+    /// blending: pixels less than half opaque are discarded.
+    pub opacity: f64,
+    /// Illumination model (`illum`): 0 is unlit, 1 diffuse only and 2 or
+    /// above adds highlights.
+    pub illumination: u32,
+    /// Diffuse texture (`map_Kd`).
+    pub diffuse_map: Option<DynamicImage>,
+    /// Specular map (`map_Ks`).
+    pub specular_map: Option<SpecularMap>,
+    /// Tangent space normal map (`norm`, or a color `map_Bump`).
+    pub normal_map: Option<NormalMap>,
+    /// Alpha map, read from the first channel (`map_d`).
+    pub alpha_map: Option<DynamicImage>,
+    /// How texture coordinates outside `[0, 1]` are brought onto the maps,
+    /// along `u` and `v`. Textures repeat by default.
+    pub wrap: [Wrap; 2],
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new(String::new())
+    }
+}
+
+impl Material {
+    /// A white material with soft highlights and no textures.
+    pub const fn new(name: String) -> Material {
+        Material {
+            name,
+            ambient: Vector3::new(1., 1., 1.),
+            diffuse: Vector3::new(1., 1., 1.),
+            specular: Vector3::new(0.5, 0.5, 0.5),
+            shininess: 32.,
+            opacity: 1.,
+            illumination: 2,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            alpha_map: None,
+            wrap: [Wrap::Repeat; 2],
+        }
+    }
+
+    pub fn with_diffuse(mut self, diffuse: Vector3<f64>) -> Material {
+        self.diffuse = diffuse;
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Vector3<f64>) -> Material {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_shininess(mut self, shininess: f64) -> Material {
+        self.shininess = shininess;
+        self
+    }
+
+    pub fn with_diffuse_map(mut self, diffuse_map: DynamicImage) -> Material {
+        self.diffuse_map = Some(diffuse_map);
+        self
+    }
+
+    pub fn with_specular_map(mut self, specular_map: SpecularMap) -> Material {
+        self.specular_map = Some(specular_map);
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Material {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    /// Texture coordinate `coord` wrapped onto the maps, see `wrap`.
+    pub fn texture_coord(&self, coord: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            self.wrap[0].apply(coord.x),
+            self.wrap[1].apply(coord.y),
+            coord.z,
+        )
+    }
+
+    /// Opacity at texture coordinate `coord`.
+    pub fn opacity_at(&self, coord: Vector3<f64>) -> f64 {
+        match &self.alpha_map {
+            Some(map) => self.opacity * f64::from(sample(map, self.texture_coord(coord))[0]) / 255.,
+            None => self.opacity,
+        }
+    }
+}
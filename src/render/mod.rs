@@ -1,5 +1,20 @@
 pub mod base;
+pub mod camera;
 pub mod common;
+pub mod depth;
+pub mod light;
+pub mod material;
 pub mod png;
+pub mod scene;
+pub mod shader;
+pub mod shadow;
+pub mod tile;
 
 pub use base::Renderer;
+pub use camera::{Camera, Projection};
+pub use depth::DepthBuffer;
+pub use light::{Attenuation, Light, LightKind};
+pub use material::Material;
+pub use scene::Scene;
+pub use shadow::{ShadowMap, ShadowSettings};
+pub use tile::{Tile, Tiled};
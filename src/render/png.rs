@@ -1,15 +1,26 @@
 use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use cgmath::Vector3;
-use image::{png, ColorType};
+use image::pnm::{PNMSubtype, SampleEncoding};
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
 use log::debug;
 
-use crate::render::Renderer;
+use crate::error::{Error, Result};
+use crate::render::tile::{Tile, Tiled};
+use crate::render::{DepthBuffer, Renderer};
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct PNG {
     pub width: u32,
     pub height: u32,
-    zindex: Vec<f64>,
+    /// Where `render` writes the finished image.
+    pub output: PathBuf,
+    /// Encoder used by `render`. When unset it is picked from the extension
+    /// of `output`.
+    pub format: Option<ImageOutputFormat>,
+    zindex: DepthBuffer,
     image: Vec<Vec<[u8; 3]>>,
 }
 
@@ -18,39 +29,29 @@ impl Renderer for PNG {
         PNG {
             width,
             height,
-            zindex: vec![std::f64::NEG_INFINITY; (width * height) as usize],
+            output: PathBuf::from("foo.png"),
+            format: None,
+            zindex: DepthBuffer::new(width, height),
             image: vec![vec![[0, 0, 0]; width as usize]; height as usize],
         }
     }
 
     fn set_pixel(&mut self, pixel: Vector3<f64>, color: [u8; 3]) {
-        if pixel.x > f64::from(self.width - 1)
-            || pixel.x < 0.
-            || pixel.y > f64::from(self.height - 1)
-            || pixel.y < 0.
-        {
-            return;
-        }
-        let zindex = (pixel.x + pixel.y * f64::from(self.width)) as usize;
-
-        if self.zindex.get(zindex).is_none() {
-            return;
-        }
-
-        if self.zindex[zindex] < pixel.z {
-            self.zindex[zindex] = pixel.z;
+        if self.zindex.test(pixel) {
             self.image[pixel.y as usize][pixel.x as usize] = color;
         }
     }
 
-    fn render(&mut self) {
-        debug!("Writing image.");
-        let flat_data = flatten(&mut self.image);
-        let buffer = File::create("foo.png").unwrap();
-        let encoder = png::PNGEncoder::new(buffer);
-        encoder
-            .encode(&flat_data, self.width, self.height, ColorType::RGB(8))
-            .expect("Error encoding PNG");
+    fn render(&mut self) -> Result<()> {
+        debug!("Writing image: {}", self.output.display());
+        let format = match &self.format {
+            Some(format) => format.clone(),
+            None => format_from_path(&self.output)?,
+        };
+        let mut buffer = BufWriter::new(File::create(&self.output)?);
+        self.write_to(&mut buffer, format)?;
+        buffer.flush()?;
+        Ok(())
     }
 
     fn get_size(&self) -> (u32, u32) {
@@ -58,16 +59,90 @@ impl Renderer for PNG {
     }
 }
 
-fn flatten(data: &mut Vec<Vec<[u8; 3]>>) -> Vec<u8> {
-    let mut flat_data: Vec<u8> = Vec::new();
+impl Tiled for PNG {
+    fn tiles(&mut self, size: u32) -> Vec<Tile<'_>> {
+        let colors = self.image.iter_mut().map(Vec::as_mut_slice);
+        Tile::split(self.width, size, self.zindex.rows_mut(), colors)
+    }
+}
+
+impl PNG {
+    pub fn with_output<P: Into<PathBuf>>(mut self, output: P) -> PNG {
+        self.output = output.into();
+        self
+    }
+
+    /// Depth buffer filled in while rendering.
+    pub fn depth(&self) -> &DepthBuffer {
+        &self.zindex
+    }
+
+    pub fn with_format(mut self, format: ImageOutputFormat) -> PNG {
+        self.format = Some(format);
+        self
+    }
+
+    /// Returns the color stored at `(x, y)`, with the origin in the bottom left
+    /// corner like the coordinates passed to `set_pixel`.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 3]> {
+        self.image
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .cloned()
+    }
+
+    /// Returns the rendered image as tightly packed RGB bytes, top row first.
+    pub fn pixels(&self) -> Vec<u8> {
+        flatten(&self.image)
+    }
+
+    /// Returns a copy of the rendered image.
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_raw(self.width, self.height, self.pixels())
+            .expect("Pixel buffer does not match the image size")
+    }
+
+    /// Encodes the rendered image into `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut W, format: ImageOutputFormat) -> Result<()> {
+        DynamicImage::ImageRgb8(self.to_image())
+            .write_to(writer, format)
+            .map_err(Error::Encode)
+    }
+}
+
+/// Looks up an encoder by name or file extension, e.g. `png` or `jpg`.
+pub fn format_from_name(name: &str) -> Option<ImageOutputFormat> {
+    match name.to_lowercase().as_str() {
+        "png" => Some(ImageOutputFormat::PNG),
+        "jpg" | "jpeg" => Some(ImageOutputFormat::JPEG(90)),
+        "bmp" => Some(ImageOutputFormat::BMP),
+        "gif" => Some(ImageOutputFormat::GIF),
+        "ico" => Some(ImageOutputFormat::ICO),
+        "ppm" | "pnm" => Some(ImageOutputFormat::PNM(PNMSubtype::Pixmap(
+            SampleEncoding::Binary,
+        ))),
+        _ => None,
+    }
+}
 
-    data.reverse();
+fn format_from_path(path: &Path) -> Result<ImageOutputFormat> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(format_from_name)
+        .ok_or_else(|| {
+            Error::Argument(format!(
+                "unable to pick an image format for {}",
+                path.display()
+            ))
+        })
+}
+
+fn flatten(data: &[Vec<[u8; 3]>]) -> Vec<u8> {
+    let mut flat_data: Vec<u8> = Vec::new();
 
-    for row in data {
+    for row in data.iter().rev() {
         for column in row {
-            for item in column.iter_mut() {
-                flat_data.push(*item);
-            }
+            flat_data.extend_from_slice(column);
         }
     }
 
@@ -0,0 +1,57 @@
+use cgmath::Vector3;
+
+use crate::render::{Camera, Light, ShadowSettings};
+
+/// Everything besides the geometry that determines how an object is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub camera: Camera,
+    pub lights: Vec<Light>,
+    /// Light reaching every surface regardless of orientation.
+    pub ambient: Vector3<f64>,
+    /// Shadows cast by directional and spot lights, if enabled.
+    pub shadows: Option<ShadowSettings>,
+}
+
+impl Default for Scene {
+    fn default() -> Scene {
+        Scene::new(Camera::default())
+    }
+}
+
+impl Scene {
+    /// Scene lit by a single white light shining along the view direction.
+    pub fn new(camera: Camera) -> Scene {
+        Scene {
+            camera,
+            lights: vec![Light::directional(camera.direction())],
+            ambient: Vector3::new(0., 0., 0.),
+            shadows: None,
+        }
+    }
+
+    /// Scene without any lights.
+    pub fn unlit(camera: Camera) -> Scene {
+        Scene {
+            camera,
+            lights: Vec::new(),
+            ambient: Vector3::new(0., 0., 0.),
+            shadows: None,
+        }
+    }
+
+    pub fn with_light(mut self, light: Light) -> Scene {
+        self.lights.push(light);
+        self
+    }
+
+    pub fn with_shadows(mut self, shadows: ShadowSettings) -> Scene {
+        self.shadows = Some(shadows);
+        self
+    }
+
+    pub fn with_ambient(mut self, ambient: Vector3<f64>) -> Scene {
+        self.ambient = ambient;
+        self
+    }
+}
@@ -0,0 +1,46 @@
+use cgmath::{Vector3, Vector4};
+
+use crate::render::shader::{Fragment, Shader, Uniforms, Vertex};
+
+/// Shows world space shading normals as colors, mapping each axis from
+/// `[-1, 1]` to `[0, 255]`. The normal map is applied if there is one.
+pub struct NormalShader;
+
+impl Shader for NormalShader {
+    type Varying = (Vector3<f64>, Vector4<f64>, Vector3<f64>);
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying) {
+        (
+            uniforms.clip(vertex.position),
+            (
+                uniforms.world_normal(vertex.normal),
+                uniforms.world_tangent(vertex.tangent),
+                vertex.texture,
+            ),
+        )
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]> {
+        let (normal, tangent, texture) = fragment.varying;
+        let normal = uniforms.shading_normal(normal, tangent, texture);
+        let channel = |value: f64| ((value + 1.) / 2. * 255.).round() as u8;
+        Some([channel(normal.x), channel(normal.y), channel(normal.z)])
+    }
+}
+
+/// Shows screen space depth as a shade of grey, lighter being closer. Also
+/// used to fill shadow maps.
+pub struct DepthShader;
+
+impl Shader for DepthShader {
+    type Varying = ();
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, ()) {
+        (uniforms.clip(vertex.position), ())
+    }
+
+    fn fragment(&self, _: &Uniforms, fragment: &Fragment<()>) -> Option<[u8; 3]> {
+        let shade = (fragment.position.z.clamp(0., 1.) * 255.).round() as u8;
+        Some([shade, shade, shade])
+    }
+}
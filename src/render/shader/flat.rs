@@ -0,0 +1,45 @@
+use cgmath::{Vector3, Vector4};
+
+use crate::render::common::shade;
+use crate::render::shader::{Fragment, Shader, Uniforms, Vertex};
+
+/// Lights every face with its geometric normal, modulating the diffuse
+/// texture.
+pub struct FlatShader;
+
+impl Shader for FlatShader {
+    type Varying = (Vector3<f64>, Vector3<f64>, Vector3<f64>);
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying) {
+        let light = uniforms.illuminate(
+            uniforms.world(vertex.position),
+            uniforms.world_normal(vertex.face_normal),
+        );
+        (
+            uniforms.clip(vertex.position),
+            (vertex.texture, vertex.color, light),
+        )
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]> {
+        let (texture, color, light) = fragment.varying;
+        Some(shade(uniforms.diffuse_color(texture, color)?, light))
+    }
+}
+
+/// Fills every pixel with the same color, ignoring lighting.
+pub struct SolidShader {
+    pub color: [u8; 3],
+}
+
+impl Shader for SolidShader {
+    type Varying = ();
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, ()) {
+        (uniforms.clip(vertex.position), ())
+    }
+
+    fn fragment(&self, _: &Uniforms, _: &Fragment<()>) -> Option<[u8; 3]> {
+        Some(self.color)
+    }
+}
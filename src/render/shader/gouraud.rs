@@ -0,0 +1,31 @@
+use cgmath::{Vector3, Vector4};
+
+use crate::render::common::{highlight, shade};
+use crate::render::shader::{Fragment, Shader, Uniforms, Vertex};
+
+/// Lights each vertex with its own normal, highlights included, and
+/// interpolates the result across the face.
+pub struct GouraudShader;
+
+impl Shader for GouraudShader {
+    type Varying = (Vector3<f64>, Vector3<f64>, Vector3<f64>, Vector3<f64>);
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying) {
+        let position = uniforms.world(vertex.position);
+        let normal = uniforms.world_normal(vertex.normal);
+        let light = uniforms.illuminate(position, normal);
+        let specular = uniforms.specular(position, normal, vertex.texture);
+        (
+            uniforms.clip(vertex.position),
+            (vertex.texture, vertex.color, light, specular),
+        )
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]> {
+        let (texture, color, light, specular) = fragment.varying;
+        Some(highlight(
+            shade(uniforms.diffuse_color(texture, color)?, light),
+            specular,
+        ))
+    }
+}
@@ -0,0 +1,422 @@
+pub mod debug;
+pub mod flat;
+pub mod gouraud;
+pub mod phong;
+pub mod toon;
+
+pub use debug::{DepthShader, NormalShader};
+pub use flat::{FlatShader, SolidShader};
+pub use gouraud::GouraudShader;
+pub use phong::PhongShader;
+pub use toon::ToonShader;
+
+use cgmath::{
+    ElementWise, EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, SquareMatrix, Vector2,
+    Vector3, Vector4,
+};
+
+use crate::render::common::{sample, shade, NormalSpace};
+use crate::render::material::{self, Material};
+use crate::render::{Light, Scene, ShadowMap};
+
+/// Attributes of a single mesh vertex handed to the vertex stage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    /// Object space position.
+    pub position: Vector3<f64>,
+    /// Object space shading normal.
+    pub normal: Vector3<f64>,
+    /// Texture coordinate.
+    pub texture: Vector3<f64>,
+    /// Color multiplied with the diffuse color of the material.
+    pub color: Vector3<f64>,
+    /// Object space tangent along increasing `u`, with the handedness of
+    /// the bitangent in `w`.
+    pub tangent: Vector4<f64>,
+    /// Geometric normal of the face the vertex belongs to.
+    pub face_normal: Vector3<f64>,
+}
+
+impl Default for Vertex {
+    fn default() -> Vertex {
+        Vertex {
+            position: Vector3::new(0., 0., 0.),
+            normal: Vector3::unit_z(),
+            texture: Vector3::new(0., 0., 0.),
+            color: Vector3::new(1., 1., 1.),
+            tangent: Vector4::new(1., 0., 0., 1.),
+            face_normal: Vector3::unit_z(),
+        }
+    }
+}
+
+/// A pixel covered by a triangle, handed to the fragment stage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fragment<V> {
+    /// Screen space position; `z` is the depth used for the depth test.
+    pub position: Vector3<f64>,
+    /// Screen space barycentric coordinates of the pixel.
+    pub barycentric: Vector3<f64>,
+    /// Perspective correct interpolation of the vertex stage outputs.
+    pub varying: V,
+}
+
+/// State shared by every vertex and fragment of a draw call.
+#[derive(Clone, Copy)]
+pub struct Uniforms<'a> {
+    /// Object to world space transform.
+    pub model: Matrix4<f64>,
+    /// Object to world space transform for normals.
+    pub normal_matrix: Matrix4<f64>,
+    /// Object to clip space transform.
+    pub transform: Matrix4<f64>,
+    pub eye: Point3<f64>,
+    pub lights: &'a [Light],
+    pub ambient: Vector3<f64>,
+    /// Shadow map of each light in `lights`, if it casts shadows.
+    pub shadows: &'a [Option<ShadowMap>],
+    /// Material of the face being drawn.
+    pub material: &'a Material,
+}
+
+impl<'a> Uniforms<'a> {
+    pub fn new(scene: &'a Scene, width: u32, height: u32) -> Uniforms<'a> {
+        let camera = &scene.camera;
+        Uniforms {
+            model: camera.model,
+            normal_matrix: camera.model.invert().unwrap_or(camera.model).transpose(),
+            transform: camera.clip_transform(width, height),
+            eye: camera.eye,
+            lights: &scene.lights,
+            ambient: scene.ambient,
+            shadows: &[],
+            material: &material::DEFAULT,
+        }
+    }
+
+    /// Transforms an object space position into clip space.
+    pub fn clip(&self, position: Vector3<f64>) -> Vector4<f64> {
+        self.transform * position.extend(1.)
+    }
+
+    /// Transforms an object space position into world space.
+    pub fn world(&self, position: Vector3<f64>) -> Vector3<f64> {
+        (self.model * position.extend(1.)).truncate()
+    }
+
+    /// Transforms an object space normal into a normalized world space one.
+    pub fn world_normal(&self, normal: Vector3<f64>) -> Vector3<f64> {
+        (self.normal_matrix * normal.extend(0.))
+            .truncate()
+            .normalize()
+    }
+
+    /// Transforms an object space tangent into a normalized world space one,
+    /// keeping the handedness in `w`.
+    pub fn world_tangent(&self, tangent: Vector4<f64>) -> Vector4<f64> {
+        (self.model * tangent.truncate().extend(0.))
+            .truncate()
+            .normalize()
+            .extend(tangent.w)
+    }
+
+    /// Shading normal at texture coordinate `texture`, perturbing the
+    /// interpolated world space `normal` with the normal map if there is
+    /// one. `tangent` is the interpolated world space tangent.
+    pub fn shading_normal(
+        &self,
+        normal: Vector3<f64>,
+        tangent: Vector4<f64>,
+        texture: Vector3<f64>,
+    ) -> Vector3<f64> {
+        let normal = normal.normalize();
+        let map = match &self.material.normal_map {
+            Some(map) => map,
+            None => return normal,
+        };
+        let mapped = map.normal(self.material.texture_coord(texture));
+        match map.space {
+            NormalSpace::Object => self.world_normal(mapped),
+            NormalSpace::Tangent => {
+                let tangent_vector = tangent.truncate();
+                let tangent_vector =
+                    (tangent_vector - normal * normal.dot(tangent_vector)).normalize();
+                let handedness = if tangent.w < 0. { -1. } else { 1. };
+                let bitangent = normal.cross(tangent_vector) * handedness;
+                (tangent_vector * mapped.x + bitangent * mapped.y + normal * mapped.z).normalize()
+            }
+        }
+    }
+
+    /// Lights reaching a surface at world space `position` facing world
+    /// space `normal`, as the direction towards the light, the unshadowed
+    /// part of its color and the cosine of the angle of incidence.
+    fn incident<'b>(
+        &'b self,
+        position: Vector3<f64>,
+        normal: Vector3<f64>,
+    ) -> impl Iterator<Item = (Vector3<f64>, Vector3<f64>, f64)> + 'b {
+        self.lights
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, source)| {
+                let (to_light, color) = source.incident(position)?;
+                let cos_angle = normal.dot(to_light);
+                if cos_angle <= 0. {
+                    return None;
+                }
+                let visibility = match self.shadows.get(index) {
+                    Some(Some(shadow)) => shadow.visibility(position, cos_angle),
+                    _ => 1.,
+                };
+                Some((to_light, color * visibility, cos_angle))
+            })
+    }
+
+    /// Sums the ambient term and the Lambertian contribution of every light
+    /// for a surface at world space `position` facing world space `normal`.
+    /// Unlit materials receive full white light.
+    pub fn illuminate(&self, position: Vector3<f64>, normal: Vector3<f64>) -> Vector3<f64> {
+        if self.material.illumination == 0 {
+            return Vector3::new(1., 1., 1.);
+        }
+        let ambient = self.ambient.mul_element_wise(self.material.ambient);
+        self.incident(position, normal)
+            .fold(ambient, |light, (_, color, cos_angle)| {
+                light + color * cos_angle
+            })
+    }
+
+    /// Sums the Blinn-Phong highlights of every light for a surface at world
+    /// space `position` facing world space `normal`, with the material
+    /// scaled by the specular map at `texture`. Materials with an
+    /// illumination model below 2 have no highlights.
+    pub fn specular(
+        &self,
+        position: Vector3<f64>,
+        normal: Vector3<f64>,
+        texture: Vector3<f64>,
+    ) -> Vector3<f64> {
+        if self.material.illumination < 2 {
+            return Vector3::new(0., 0., 0.);
+        }
+        let factor = match &self.material.specular_map {
+            Some(map) => map.factor(self.material.texture_coord(texture)),
+            None => 1.,
+        };
+        let strength = self.material.specular * factor;
+        let exponent = (self.material.shininess * factor).max(1.);
+        let to_eye = (self.eye.to_vec() - position).normalize();
+        self.incident(position, normal).fold(
+            Vector3::new(0., 0., 0.),
+            |light, (to_light, color, _)| {
+                let half = (to_light + to_eye).normalize();
+                let cos_half = normal.dot(half).max(0.);
+                light + color.mul_element_wise(strength) * cos_half.powf(exponent)
+            },
+        )
+    }
+
+    /// Diffuse color of the material at `texture`, tinted by its diffuse
+    /// map if it has one and by the interpolated vertex `color`. `None` where
+    /// the material is mostly transparent, so that shaders can discard the
+    /// pixel.
+    pub fn diffuse_color(&self, texture: Vector3<f64>, color: Vector3<f64>) -> Option<[u8; 3]> {
+        if self.material.opacity_at(texture) < 0.5 {
+            return None;
+        }
+        let texel = match &self.material.diffuse_map {
+            Some(image) => sample(image, self.material.texture_coord(texture)),
+            None => [255, 255, 255],
+        };
+        // Rounded to the precision of 8-bit colors, so that interpolating
+        // white across a face does not darken it by a shade.
+        let color = color.map(|channel| (channel * 255.).round() / 255.);
+        Some(shade(texel, self.material.diffuse.mul_element_wise(color)))
+    }
+}
+
+/// Programmable stages run by the rasterizer.
+///
+/// `vertex` is run once per triangle corner and returns its clip space
+/// position along with any values that should be interpolated across the
+/// triangle. `fragment` is run per covered pixel and returns the color to
+/// write, or `None` to discard the pixel.
+pub trait Shader {
+    type Varying: Varying;
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying);
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]>;
+}
+
+/// Values that can be interpolated across a triangle.
+pub trait Varying: Copy {
+    /// Returns `a * weights.x + b * weights.y + c * weights.z`.
+    fn weighted(a: &Self, b: &Self, c: &Self, weights: Vector3<f64>) -> Self;
+}
+
+impl Varying for () {
+    fn weighted(_: &(), _: &(), _: &(), _: Vector3<f64>) {}
+}
+
+impl Varying for f64 {
+    fn weighted(a: &f64, b: &f64, c: &f64, weights: Vector3<f64>) -> f64 {
+        a * weights.x + b * weights.y + c * weights.z
+    }
+}
+
+macro_rules! impl_varying_vector {
+    ($($vector:ident),*) => {
+        $(
+            impl Varying for $vector<f64> {
+                fn weighted(a: &Self, b: &Self, c: &Self, weights: Vector3<f64>) -> Self {
+                    a * weights.x + b * weights.y + c * weights.z
+                }
+            }
+        )*
+    };
+}
+
+impl_varying_vector!(Vector2, Vector3, Vector4);
+
+macro_rules! impl_varying_tuple {
+    ($(($($name:ident: $index:tt),*)),*) => {
+        $(
+            impl<$($name: Varying),*> Varying for ($($name,)*) {
+                fn weighted(a: &Self, b: &Self, c: &Self, weights: Vector3<f64>) -> Self {
+                    ($($name::weighted(&a.$index, &b.$index, &c.$index, weights),)*)
+                }
+            }
+        )*
+    };
+}
+
+impl_varying_tuple!(
+    (A: 0, B: 1),
+    (A: 0, B: 1, C: 2),
+    (A: 0, B: 1, C: 2, D: 3),
+    (A: 0, B: 1, C: 2, D: 3, E: 4)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::common::{SpecularMap, Wrap};
+    use image::DynamicImage;
+
+    #[test]
+    fn weighted_tuple() {
+        let (a, b, c) = (
+            (1., Vector2::new(0., 0.)),
+            (2., Vector2::new(4., 0.)),
+            (3., Vector2::new(0., 8.)),
+        );
+        let (scalar, vector) = Varying::weighted(&a, &b, &c, Vector3::new(0.5, 0.25, 0.25));
+
+        assert!((scalar - 1.75).abs() < 1e-12);
+        assert_eq!(vector, Vector2::new(1., 2.));
+    }
+
+    #[test]
+    fn specular_highlight() {
+        let scene = Scene::default();
+        let mut uniforms = Uniforms::new(&scene, 10, 10);
+        let position = Vector3::new(0., 0., 0.);
+        let texture = Vector3::new(0.5, 0.5, 0.);
+
+        let facing = uniforms.specular(position, Vector3::unit_z(), texture);
+        assert!((facing - uniforms.material.specular).magnitude() < 1e-9);
+
+        let tilted = Vector3::new(0., 1., 2.).normalize();
+        let broad = Material::default().with_shininess(8.);
+        let sharp = Material::default().with_shininess(128.);
+        uniforms.material = &broad;
+        let broad = uniforms.specular(position, tilted, texture).x;
+        uniforms.material = &sharp;
+        let sharp = uniforms.specular(position, tilted, texture).x;
+        assert!(broad > sharp && sharp > 0.);
+
+        let dull = Material::default().with_specular_map(SpecularMap {
+            image: DynamicImage::ImageRgb8(image::RgbImage::new(2, 2)),
+        });
+        uniforms.material = &dull;
+        assert_eq!(
+            uniforms.specular(position, Vector3::unit_z(), texture),
+            Vector3::new(0., 0., 0.)
+        );
+    }
+
+    #[test]
+    fn material_colors() {
+        let scene = Scene::default().with_ambient(Vector3::new(0.5, 0.5, 0.5));
+        let mut uniforms = Uniforms::new(&scene, 10, 10);
+        let texture = Vector3::new(0., 0., 0.);
+
+        let tinted = Material {
+            ambient: Vector3::new(0.2, 0., 0.),
+            ..Material::default().with_diffuse(Vector3::new(1., 0.5, 0.))
+        };
+        uniforms.material = &tinted;
+        assert_eq!(
+            uniforms.diffuse_color(texture, Vector3::new(1., 1., 1.)),
+            Some([255, 127, 0])
+        );
+        assert_eq!(
+            uniforms.diffuse_color(texture, Vector3::new(0.5, 1., 1.)),
+            Some([128, 127, 0])
+        );
+        let back = uniforms.illuminate(Vector3::new(0., 0., 0.), -Vector3::unit_z());
+        assert!((back - Vector3::new(0.1, 0., 0.)).magnitude() < 1e-9);
+
+        let unlit = Material {
+            illumination: 0,
+            ..Material::default()
+        };
+        uniforms.material = &unlit;
+        assert_eq!(
+            uniforms.illuminate(Vector3::new(0., 0., 0.), -Vector3::unit_z()),
+            Vector3::new(1., 1., 1.)
+        );
+
+        let transparent = Material {
+            opacity: 0.25,
+            ..Material::default()
+        };
+        uniforms.material = &transparent;
+        assert_eq!(
+            uniforms.diffuse_color(texture, Vector3::new(1., 1., 1.)),
+            None
+        );
+    }
+
+    #[test]
+    fn wrapped_texture() {
+        let scene = Scene::default();
+        let mut uniforms = Uniforms::new(&scene, 10, 10);
+        // A red texel on the left and a blue one on the right.
+        let mut image = image::RgbImage::new(2, 1);
+        image.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        image.put_pixel(1, 0, image::Rgb([0, 0, 255]));
+        let repeated = Material::default().with_diffuse_map(DynamicImage::ImageRgb8(image));
+        let mirrored = Material {
+            wrap: [Wrap::MirroredRepeat, Wrap::Repeat],
+            ..repeated.clone()
+        };
+        let clamped = Material {
+            wrap: [Wrap::Clamp, Wrap::Repeat],
+            ..repeated.clone()
+        };
+        let color = |uniforms: &Uniforms, u: f64| {
+            uniforms.diffuse_color(Vector3::new(u, 0.5, 0.), Vector3::new(1., 1., 1.))
+        };
+
+        uniforms.material = &repeated;
+        assert_eq!(color(&uniforms, 1.1), Some([255, 0, 0]));
+        assert_eq!(color(&uniforms, -0.1), Some([0, 0, 255]));
+        uniforms.material = &mirrored;
+        assert_eq!(color(&uniforms, 1.1), Some([0, 0, 255]));
+        uniforms.material = &clamped;
+        assert_eq!(color(&uniforms, 1.5), Some([0, 0, 255]));
+        assert_eq!(color(&uniforms, -0.5), Some([255, 0, 0]));
+    }
+}
@@ -0,0 +1,42 @@
+use cgmath::{Vector3, Vector4};
+
+use crate::render::common::{highlight, shade};
+use crate::render::shader::{Fragment, Shader, Uniforms, Vertex};
+
+/// Interpolates the vertex normals across the face and lights every pixel
+/// with Blinn-Phong highlights, applying the normal map if there is one.
+pub struct PhongShader;
+
+impl Shader for PhongShader {
+    type Varying = (
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector4<f64>,
+    );
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying) {
+        (
+            uniforms.clip(vertex.position),
+            (
+                vertex.texture,
+                vertex.color,
+                uniforms.world_normal(vertex.normal),
+                uniforms.world(vertex.position),
+                uniforms.world_tangent(vertex.tangent),
+            ),
+        )
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]> {
+        let (texture, color, normal, position, tangent) = fragment.varying;
+        let normal = uniforms.shading_normal(normal, tangent, texture);
+        let light = uniforms.illuminate(position, normal);
+        let specular = uniforms.specular(position, normal, texture);
+        Some(highlight(
+            shade(uniforms.diffuse_color(texture, color)?, light),
+            specular,
+        ))
+    }
+}
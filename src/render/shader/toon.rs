@@ -0,0 +1,55 @@
+use cgmath::{Vector3, Vector4};
+
+use crate::render::common::{highlight, shade};
+use crate::render::shader::{Fragment, Shader, Uniforms, Vertex};
+
+/// Cel shading: the light reaching each pixel is quantized into a few flat
+/// bands, with a hard edged highlight where the specular term is strong.
+pub struct ToonShader {
+    pub bands: u32,
+}
+
+impl Default for ToonShader {
+    fn default() -> ToonShader {
+        ToonShader { bands: 4 }
+    }
+}
+
+impl Shader for ToonShader {
+    type Varying = (
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector3<f64>,
+        Vector4<f64>,
+    );
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Self::Varying) {
+        (
+            uniforms.clip(vertex.position),
+            (
+                vertex.texture,
+                vertex.color,
+                uniforms.world_normal(vertex.normal),
+                uniforms.world(vertex.position),
+                uniforms.world_tangent(vertex.tangent),
+            ),
+        )
+    }
+
+    fn fragment(&self, uniforms: &Uniforms, fragment: &Fragment<Self::Varying>) -> Option<[u8; 3]> {
+        let (texture, color, normal, position, tangent) = fragment.varying;
+        let normal = uniforms.shading_normal(normal, tangent, texture);
+        let light = uniforms.illuminate(position, normal);
+        let bands = f64::from(self.bands.max(1));
+        let light = light.map(|channel| (channel * bands).ceil() / bands);
+        let specular =
+            uniforms
+                .specular(position, normal, texture)
+                .map(|channel| if channel > 0.5 { 1. } else { 0. });
+        Some(highlight(
+            shade(uniforms.diffuse_color(texture, color)?, light),
+            specular,
+        ))
+    }
+}
@@ -0,0 +1,134 @@
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+use crate::render::{Camera, DepthBuffer, LightKind, Projection, Renderer};
+
+/// Controls the shadow mapping pass run before the main one.
+///
+/// Shadows are looked up wherever a shader lights a surface, so shaders
+/// lighting per pixel such as `PhongShader` get per pixel shadows while
+/// `FlatShader` and `GouraudShader` only sample them at the vertices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// Width and height of each shadow map.
+    pub resolution: u32,
+    /// Constant depth offset applied before comparing against the map, to
+    /// keep surfaces from shadowing themselves.
+    pub bias: f64,
+    /// Additional offset scaled by how steeply the light hits the surface.
+    pub slope_bias: f64,
+    /// Radius in texels of the percentage-closer filter. Zero gives hard
+    /// edges.
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            resolution: 1024,
+            bias: 0.002,
+            slope_bias: 0.004,
+            pcf_radius: 1,
+        }
+    }
+}
+
+/// Depth of the scene as seen from a light.
+pub struct ShadowMap {
+    pub depth: DepthBuffer,
+    /// World space to shadow map screen space transform.
+    pub transform: Matrix4<f64>,
+    pub settings: ShadowSettings,
+}
+
+impl ShadowMap {
+    /// Camera rendering the depth seen from a light, covering the sphere at
+    /// `center` with `radius` in world space. Point lights have no shadow
+    /// maps.
+    pub fn camera(kind: &LightKind, center: Point3<f64>, radius: f64) -> Option<Camera> {
+        let radius = radius.max(f64::EPSILON);
+        match *kind {
+            LightKind::Directional { direction } => Some(Camera {
+                eye: center - direction * radius * 2.,
+                target: center,
+                up: up_for(direction),
+                projection: Projection::Orthographic {
+                    height: radius * 2.,
+                },
+                near: radius * 0.5,
+                far: radius * 3.5,
+                ..Camera::default()
+            }),
+            LightKind::Spot {
+                position,
+                direction,
+                outer,
+                ..
+            } => {
+                let eye = Point3::from_vec(position);
+                let distance = (center - eye).magnitude();
+                Some(Camera {
+                    eye,
+                    target: eye + direction,
+                    up: up_for(direction),
+                    projection: Projection::Perspective {
+                        fovy: Deg((outer.0 * 2.).min(170.)),
+                    },
+                    near: (distance - radius).max(distance * 0.01).max(1e-3),
+                    far: distance + radius,
+                    ..Camera::default()
+                })
+            }
+            LightKind::Point { .. } => None,
+        }
+    }
+
+    /// Wraps a depth buffer rendered by `camera`.
+    pub fn new(depth: DepthBuffer, camera: &Camera, settings: ShadowSettings) -> ShadowMap {
+        let (width, height) = depth.get_size();
+        let aspect = f64::from(width) / f64::from(height);
+        let transform = Camera::viewport(width, height) * camera.projection(aspect) * camera.view();
+        ShadowMap {
+            depth,
+            transform,
+            settings,
+        }
+    }
+
+    /// Fraction of the light reaching world space `position`, where
+    /// `cos_angle` is the cosine between the surface normal and the
+    /// direction to the light.
+    pub fn visibility(&self, position: Vector3<f64>, cos_angle: f64) -> f64 {
+        let point = match Camera::project(&self.transform, position) {
+            Some(point) => point,
+            None => return 1.,
+        };
+
+        let cos_angle = cos_angle.clamp(0.05, 1.);
+        let slope = (1. - cos_angle * cos_angle).sqrt() / cos_angle;
+        let depth = point.z + self.settings.bias + self.settings.slope_bias * slope;
+
+        let radius = i64::from(self.settings.pcf_radius);
+        let (mut lit, mut samples) = (0, 0);
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                let x = (point.x + dx as f64).round();
+                let y = (point.y + dy as f64).round();
+                samples += 1;
+                match self.depth.depth(x, y) {
+                    Some(closest) if closest > depth => {}
+                    _ => lit += 1,
+                }
+            }
+        }
+        f64::from(lit) / f64::from(samples)
+    }
+}
+
+/// Any vector not parallel to `direction`, to orient a light camera.
+fn up_for(direction: Vector3<f64>) -> Vector3<f64> {
+    if direction.normalize().y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
@@ -0,0 +1,91 @@
+use cgmath::Vector3;
+
+use crate::render::Renderer;
+
+/// Side in pixels of the square tiles that `Mesh::render_parallel` splits
+/// the image into.
+pub const TILE_SIZE: u32 = 32;
+
+/// Render targets that can be split into tiles, which are drawn to in
+/// parallel since they share no pixels.
+pub trait Tiled: Renderer {
+    /// Splits the target into tiles of `size` pixels a side, smaller along
+    /// the right and top edges, ordered left to right and bottom to top.
+    fn tiles(&mut self, size: u32) -> Vec<Tile<'_>>;
+}
+
+/// A rectangle of a render target's pixels, borrowed from it.
+pub struct Tile<'a> {
+    /// Left column of the tile.
+    pub x: u32,
+    /// Bottom row of the tile.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Rows of the depth buffer covered by the tile, bottom row first.
+    depths: Vec<&'a mut [f64]>,
+    /// Rows of colors covered by the tile, or none for depth only targets.
+    colors: Vec<&'a mut [[u8; 3]]>,
+}
+
+impl<'a> Tile<'a> {
+    /// Splits the rows of a depth buffer and, unless `colors` is empty, of
+    /// a color buffer into tiles. Both hold the rows of a `width` pixel wide
+    /// image, bottom row first.
+    pub(crate) fn split(
+        width: u32,
+        size: u32,
+        depths: impl Iterator<Item = &'a mut [f64]>,
+        colors: impl Iterator<Item = &'a mut [[u8; 3]]>,
+    ) -> Vec<Tile<'a>> {
+        let columns = ((width + size - 1) / size) as usize;
+        let mut tiles: Vec<Tile> = Vec::new();
+        for (y, depth_row) in depths.enumerate() {
+            let y = y as u32;
+            if y % size == 0 {
+                tiles.extend((0..columns as u32).map(|column| Tile {
+                    x: column * size,
+                    y,
+                    width: size.min(width - column * size),
+                    height: 0,
+                    depths: Vec::new(),
+                    colors: Vec::new(),
+                }));
+            }
+            let band = tiles.len() - columns;
+            for (column, depths) in depth_row.chunks_mut(size as usize).enumerate() {
+                let tile = &mut tiles[band + column];
+                tile.depths.push(depths);
+                tile.height += 1;
+            }
+        }
+        for (y, color_row) in colors.enumerate() {
+            let band = y / size as usize * columns;
+            for (column, colors) in color_row.chunks_mut(size as usize).enumerate() {
+                tiles[band + column].colors.push(colors);
+            }
+        }
+        tiles
+    }
+
+    /// Depth tests a pixel given in image coordinates like
+    /// `Renderer::set_pixel`, storing its depth and color if it is closer.
+    /// Pixels outside the tile are ignored.
+    pub fn set_pixel(&mut self, pixel: Vector3<f64>, color: [u8; 3]) {
+        if pixel.x < 0. || pixel.y < 0. {
+            return;
+        }
+        let (x, y) = (pixel.x as u32, pixel.y as u32);
+        if x < self.x || y < self.y || x - self.x >= self.width || y - self.y >= self.height {
+            return;
+        }
+        let (column, row) = ((x - self.x) as usize, (y - self.y) as usize);
+        let depth = &mut self.depths[row][column];
+        if *depth < pixel.z {
+            *depth = pixel.z;
+            if let Some(colors) = self.colors.get_mut(row) {
+                colors[column] = color;
+            }
+        }
+    }
+}
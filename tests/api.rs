@@ -0,0 +1,249 @@
+use cpuengine::model;
+use cpuengine::obj::Object;
+use cpuengine::render::shader::{Fragment, PhongShader, Shader, Uniforms, Vertex};
+use cpuengine::render::{Scene, ShadowSettings};
+use cpuengine::{Line, Mesh, Renderer, PNG};
+
+use cgmath::{Vector3, Vector4};
+use image::GenericImageView;
+
+#[test]
+fn render_object() {
+    let texture = image::open("tests/fixtures/obj/head_diffuse.tga")
+        .unwrap()
+        .flipv();
+    let mesh = Object::new("tests/fixtures/obj/head.obj")
+        .unwrap()
+        .to_mesh()
+        .with_texture(texture);
+
+    let mut renderer: PNG = Renderer::new(64, 64);
+    mesh.render(&mut renderer, &Scene::default()).unwrap();
+
+    let pixels = renderer.pixels();
+    assert_eq!(pixels.len(), 64 * 64 * 3);
+    assert!(pixels.iter().any(|channel| *channel != 0));
+    assert_eq!(renderer.get_pixel(0, 0), Some([0, 0, 0]));
+}
+
+#[test]
+fn render_parallel() {
+    let texture = image::open("tests/fixtures/obj/head_diffuse.tga")
+        .unwrap()
+        .flipv();
+    let mesh = Object::new("tests/fixtures/obj/head.obj")
+        .unwrap()
+        .to_mesh()
+        .with_texture(texture);
+    let scene = Scene::default().with_shadows(ShadowSettings::default());
+
+    let mut serial: PNG = Renderer::new(150, 100);
+    mesh.render_with(&mut serial, &scene, &PhongShader).unwrap();
+    let mut parallel: PNG = Renderer::new(150, 100);
+    mesh.render_parallel(&mut parallel, &scene, &PhongShader)
+        .unwrap();
+    assert_eq!(parallel.pixels(), serial.pixels());
+}
+
+#[test]
+fn render_line() {
+    let line = Line::new(
+        Vector3::new(0., 0., 0.),
+        Vector3::new(8., 8., 0.),
+        [255, 0, 0],
+    )
+    .unwrap();
+
+    let mut renderer: PNG = Renderer::new(10, 10);
+    line.render(&mut renderer);
+
+    assert_eq!(renderer.get_pixel(3, 3), Some([255, 0, 0]));
+    assert_eq!(renderer.get_pixel(3, 4), Some([0, 0, 0]));
+    assert_eq!(renderer.get_pixel(10, 10), None);
+}
+
+#[test]
+fn render_clipped_lines() {
+    let color = [255, 0, 0];
+    let mut renderer: PNG = Renderer::new(10, 10);
+    Line::new(Vector3::new(4., 1., 0.), Vector3::new(4., 8., 0.), color)
+        .unwrap()
+        .render(&mut renderer);
+    assert_eq!(renderer.get_pixel(4, 1), Some(color));
+    assert_eq!(renderer.get_pixel(4, 8), Some(color));
+
+    // Only the part of the line inside the image is stepped along.
+    Line::new(Vector3::new(0., 0., 0.), Vector3::new(1e12, 5., 0.), color)
+        .unwrap()
+        .render(&mut renderer);
+    assert_eq!(renderer.get_pixel(9, 0), Some(color));
+}
+
+#[test]
+fn render_in_memory() {
+    let mut renderer: PNG = Renderer::new(4, 2);
+    renderer.set_pixel(Vector3::new(1., 0., 0.), [10, 20, 30]);
+
+    let image = renderer.to_image();
+    assert_eq!(image.dimensions(), (4, 2));
+    assert_eq!(image.get_pixel(1, 1).data, [10, 20, 30]);
+
+    let mut encoded: Vec<u8> = Vec::new();
+    renderer
+        .write_to(&mut encoded, image::ImageOutputFormat::PNG)
+        .unwrap();
+    assert_eq!(&encoded[1..4], b"PNG");
+}
+
+#[test]
+fn render_to_output() {
+    let output = std::env::temp_dir().join(format!(
+        "cpuengine-render-to-output-{}.bmp",
+        std::process::id()
+    ));
+    let mut renderer: PNG = Renderer::new(4, 4);
+    renderer = renderer.with_output(&output);
+    renderer.render().unwrap();
+
+    let image = image::open(&output).unwrap();
+    assert_eq!(image.width(), 4);
+    std::fs::remove_file(output).unwrap();
+}
+
+/// Colors pixels by their texture coordinate and discards the left half.
+struct TextureCoordinateShader;
+
+impl Shader for TextureCoordinateShader {
+    type Varying = Vector3<f64>;
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f64>, Vector3<f64>) {
+        (uniforms.clip(vertex.position), vertex.texture)
+    }
+
+    fn fragment(&self, _: &Uniforms, fragment: &Fragment<Vector3<f64>>) -> Option<[u8; 3]> {
+        if fragment.position.x < 32. {
+            return None;
+        }
+        Some([(fragment.varying.x * 255.) as u8, 0, 255])
+    }
+}
+
+#[test]
+fn render_custom_shader() {
+    let object = Object::new("tests/fixtures/obj/head.obj").unwrap();
+
+    let mut renderer: PNG = Renderer::new(64, 64);
+    object
+        .to_mesh()
+        .render_with(&mut renderer, &Scene::default(), &TextureCoordinateShader)
+        .unwrap();
+
+    assert_eq!(renderer.get_pixel(20, 32), Some([0, 0, 0]));
+    assert_eq!(renderer.get_pixel(40, 32).map(|pixel| pixel[2]), Some(255));
+}
+
+#[test]
+fn load_materials() {
+    let object = Object::new("tests/fixtures/obj/testobj.obj").unwrap();
+
+    assert_eq!(object.materials.len(), 1);
+    let material = &object.materials[0];
+    assert_eq!(material.name, "Material.001");
+    assert_eq!(material.diffuse, Vector3::new(0.8, 0.8, 0.8));
+    assert_eq!(material.shininess, 225.);
+    assert!(material.diffuse_map.is_none());
+    assert_eq!(object.face_materials, vec![0; 4]);
+}
+
+#[test]
+fn convert_to_mesh() {
+    let object = Object::new("tests/fixtures/obj/testobj.obj").unwrap();
+    let mesh: Mesh = object.to_mesh();
+
+    let triangles: usize = object.triangles.iter().map(Vec::len).sum();
+    assert_eq!(mesh.triangles.len(), triangles);
+    assert_eq!(mesh.triangle_materials, vec![0; triangles]);
+    assert_eq!(mesh.normals.len(), mesh.positions.len());
+    assert_eq!(mesh.material(0).name, "Material.001");
+}
+
+#[test]
+fn load_stl() {
+    let ascii = model::load("tests/fixtures/stl/tetrahedron_ascii.stl").unwrap();
+    let binary = model::load("tests/fixtures/stl/tetrahedron.stl").unwrap();
+
+    for mesh in &[ascii, binary] {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles.len(), 4);
+        let mut renderer: PNG = Renderer::new(32, 32);
+        mesh.render(&mut renderer, &Scene::default()).unwrap();
+        assert!(renderer.pixels().iter().any(|channel| *channel != 0));
+    }
+}
+
+#[test]
+fn load_ply() {
+    let cube = model::load("tests/fixtures/ply/cube.ply").unwrap();
+    assert_eq!(cube.positions.len(), 8);
+    assert_eq!(cube.colors.len(), 8);
+    assert_eq!(cube.triangles.len(), 12);
+
+    let points = model::load("tests/fixtures/ply/points.ply").unwrap();
+    assert_eq!(points.points.len(), 200);
+    assert!(points.triangles.is_empty());
+    let mut renderer: PNG = Renderer::new(32, 32);
+    points.render(&mut renderer, &Scene::default()).unwrap();
+    // Points near the center are blue, from their vertex colors.
+    assert!(renderer
+        .pixels()
+        .chunks(3)
+        .any(|pixel| pixel[2] > 200 && pixel[0] < 50));
+}
+
+#[test]
+fn load_gltf() {
+    for path in &[
+        "tests/fixtures/gltf/quad.gltf",
+        "tests/fixtures/gltf/quad.glb",
+    ] {
+        let mesh = model::load(path).unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.materials[0].name, "checker");
+
+        let mut renderer: PNG = Renderer::new(40, 40);
+        mesh.render(&mut renderer, &Scene::default()).unwrap();
+        // The quad is moved right by its parent node, and the red half of
+        // its texture is at the top.
+        assert_eq!(renderer.get_pixel(10, 20), Some([0, 0, 0]));
+        assert_eq!(renderer.get_pixel(22, 27), Some([255, 0, 0]));
+        assert_eq!(renderer.get_pixel(17, 12), Some([0, 0, 255]));
+    }
+}
+
+#[test]
+fn save_models() {
+    let directory =
+        std::env::temp_dir().join(format!("cpuengine-save-models-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let mesh = model::load("tests/fixtures/gltf/quad.gltf").unwrap();
+    let render = |mesh: &Mesh| {
+        let mut renderer: PNG = Renderer::new(40, 40);
+        mesh.render(&mut renderer, &Scene::default()).unwrap();
+        renderer.pixels().to_vec()
+    };
+
+    for extension in &["obj", "ply", "stl"] {
+        let path = directory.join(format!("quad.{}", extension));
+        model::save(&mesh, &path).unwrap();
+        let saved = model::load(&path).unwrap();
+        assert_eq!(saved.positions.len(), 4);
+        assert_eq!(saved.triangles.len(), 2);
+        if *extension == "obj" {
+            // Materials and their textures are kept in OBJ files.
+            assert_eq!(render(&saved), render(&mesh));
+        }
+    }
+    assert!(model::save(&mesh, directory.join("quad.gltf")).is_err());
+    assert!(model::save(&mesh, directory.join("quad.txt")).is_err());
+    std::fs::remove_dir_all(&directory).unwrap();
+}